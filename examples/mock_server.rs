@@ -0,0 +1,39 @@
+//! Integration test of an application embedding the library, without network access: the catalog and
+//! the gateway are served by the in-process mock.
+//! Run with `cargo run --example mock_server --features test-util`
+use blockfrost::BlockFrostApi;
+use book_cli::{mock::MockServer, CollectionSource, CoverFetcher};
+use std::error::Error;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let policy_id = "b4d2bc5b6a08ae3b9bbd2e5bd6ec4a5a1d34ef2e8e9e7fa7ef2bb0a4";
+    //`hello world\n` added to ipfs
+    let cid = "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o";
+    let content = b"hello world\n";
+
+    let mock = MockServer::start().await?;
+    mock.add_collection(policy_id);
+    mock.add_content(cid, &content[..]);
+    //the first gateway request fails, the fetcher retries it
+    mock.fail_next(cid, 1);
+
+    let collections = CollectionSource::Url(mock.collections_url())
+        .collections()
+        .await?;
+    assert!(collections.is_some_and(|ids| ids.contains(policy_id)));
+
+    //downloads only use the gateway, Blockfrost isn't called
+    let api = BlockFrostApi::new("<project id>", Default::default());
+    let fetcher = CoverFetcher::new(api, &mock.gateway_url());
+    let (_, cover) = fetcher.download(cid).await?;
+    assert_eq!(&cover[..], content);
+    assert_eq!(mock.requests(cid), 2);
+
+    println!(
+        "downloaded {} bytes from the mock gateway after {} requests",
+        cover.len(),
+        mock.requests(cid)
+    );
+    Ok(())
+}
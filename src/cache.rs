@@ -0,0 +1,91 @@
+use crate::{storage, CoverFetcher};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+};
+use tokio::sync::Mutex;
+
+/// Local copy of an asset's cover, see [`CoverFetcher::get_cover`]
+#[derive(Debug, Clone)]
+pub struct CoverHandle {
+    pub asset: String,
+    pub path: PathBuf,
+    /// false if it was already in the cache dir
+    pub downloaded: bool,
+}
+
+impl CoverHandle {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl CoverFetcher {
+    /// directory where [`CoverFetcher::get_cover`] keeps the covers, required to use it.
+    /// Its own directory: the covers it downloads aren't recorded in a work dir manifest
+    pub fn with_cache_dir(mut self, cache_dir: impl AsRef<Path>) -> CoverFetcher {
+        self.cache_dir = Some(cache_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Read-through access to an asset's cover: the cached file if there's one, otherwise the cover
+    /// is downloaded on demand. `None` if the asset has no high-res cover.
+    /// Concurrent requests for the same asset (or cid) wait for a single download.
+    /// Covers are named as the cli does, but they aren't recorded in a manifest.
+    /// Fails if no cache dir was set with [`CoverFetcher::with_cache_dir`].
+    pub async fn get_cover(
+        &self,
+        asset: &str,
+    ) -> Result<Option<CoverHandle>, Box<dyn Error + Send + Sync>> {
+        let cache_dir = self
+            .cache_dir
+            .as_deref()
+            .ok_or("no cache dir to keep the covers in, set one with `with_cache_dir`")?;
+        if let Some(path) = storage::find_cover(cache_dir, asset) {
+            return Ok(Some(CoverHandle {
+                asset: asset.to_owned(),
+                path,
+                downloaded: false,
+            }));
+        }
+
+        let lock = self.inflight_lock(asset);
+        let _guard = lock.lock().await;
+        //another request may have downloaded it while waiting
+        if let Some(path) = storage::find_cover(cache_dir, asset) {
+            return Ok(Some(CoverHandle {
+                asset: asset.to_owned(),
+                path,
+                downloaded: false,
+            }));
+        }
+
+        let cover = match self.cover(asset).await? {
+            Some(cover) => cover,
+            None => return Ok(None),
+        };
+
+        let (_, data) = self.download(&cover.cid).await?;
+        let path = cache_dir.join(storage::cover_file_name(asset, &data));
+        storage::write_file(&path.to_string_lossy(), &data).await?;
+        Ok(Some(CoverHandle {
+            asset: asset.to_owned(),
+            path,
+            downloaded: true,
+        }))
+    }
+
+    /// lock held while an asset's cover is being fetched and written, shared by the requests for it
+    fn inflight_lock(&self, asset: &str) -> Arc<Mutex<()>> {
+        let mut inflight = self.inflight.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(lock) = inflight.get(asset).and_then(Weak::upgrade) {
+            return lock;
+        }
+        //forget the assets nobody is waiting for anymore
+        inflight.retain(|_, lock| lock.strong_count() > 0);
+        let lock = Arc::new(Mutex::new(()));
+        inflight.insert(asset.to_owned(), Arc::downgrade(&lock));
+        lock
+    }
+}
@@ -0,0 +1,84 @@
+use reqwest::StatusCode;
+use std::sync::Mutex;
+
+/// Fault injected in place of (or on top of) a real response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// the gateway answers 504
+    GatewayTimeout,
+    /// the gateway answers 429
+    TooManyRequests,
+    /// the body is cut in half
+    TruncatedBody,
+    /// the Blockfrost call fails
+    BlockfrostError,
+}
+
+/// Randomly injects gateway and Blockfrost faults, reproducibly from a seed, to check that
+/// interrupted and retried runs converge to a consistent work dir
+#[derive(Debug)]
+pub struct Chaos {
+    state: Mutex<u64>,
+    rate: f64,
+}
+
+impl Chaos {
+    /// faults are injected in 20% of the calls
+    pub fn new(seed: u64) -> Chaos {
+        Chaos {
+            state: Mutex::new(seed),
+            rate: 0.2,
+        }
+    }
+
+    /// fraction of the calls that fail, between 0 and 1
+    pub fn with_rate(mut self, rate: f64) -> Chaos {
+        self.rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// fault to inject in a gateway download, if any
+    pub fn gateway_fault(&self) -> Option<Fault> {
+        if !self.strikes() {
+            return None;
+        }
+        match self.next() % 3 {
+            0 => Some(Fault::GatewayTimeout),
+            1 => Some(Fault::TooManyRequests),
+            _ => Some(Fault::TruncatedBody),
+        }
+    }
+
+    /// fault to inject in a Blockfrost call, if any
+    pub fn blockfrost_fault(&self) -> Option<Fault> {
+        self.strikes().then_some(Fault::BlockfrostError)
+    }
+
+    fn strikes(&self) -> bool {
+        //53 random bits mapped to [0, 1)
+        let sample = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        sample < self.rate
+    }
+
+    /// splitmix64, good enough for picking faults and needs no dependency
+    fn next(&self) -> u64 {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        *state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// the error reqwest gives for a response with `status`, as if a server had answered it
+pub(crate) fn status_error(status: StatusCode) -> reqwest::Error {
+    let response = http::Response::builder()
+        .status(status)
+        .body(Vec::new())
+        .map(reqwest::Response::from)
+        .expect("valid status");
+    response
+        .error_for_status()
+        .expect_err("only error statuses are injected")
+}
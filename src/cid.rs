@@ -0,0 +1,251 @@
+use sha2::{Digest, Sha256};
+
+/// multicodec of the blocks: dag-pb (UnixFS nodes) and raw (file content as is)
+const DAG_PB: u8 = 0x70;
+const RAW: u8 = 0x55;
+/// sha2-256 multihash prefix (code, digest length)
+const SHA2_256: [u8; 2] = [0x12, 0x20];
+/// `ipfs add` defaults: fixed size chunks, balanced dag
+const CHUNK_SIZE: usize = 256 * 1024;
+const MAX_LINKS: usize = 174;
+
+/// Decodes a CIDv0 (`Qm...`) or base32 CIDv1 (`bafy...`) to its binary form, `None` for other
+/// encodings or cids with a path
+pub fn decode(cid: &str) -> Option<Vec<u8>> {
+    if cid.len() == 46 && cid.starts_with("Qm") {
+        base58_decode(cid)
+    } else {
+        base32_decode(cid.strip_prefix('b')?)
+    }
+}
+
+/// Binary cids `data` gets when added to ipfs with the default settings: CIDv0, CIDv1 (raw leaves,
+/// or dag-pb ones as in older versions) and the whole content as a single raw block.
+/// Computed locally, so files can be matched to cids without downloading them.
+pub fn local_cids(data: &[u8]) -> Vec<Vec<u8>> {
+    vec![
+        unixfs_root(data, 0, false),
+        unixfs_root(data, 1, true),
+        unixfs_root(data, 1, false),
+        cid_bytes(1, RAW, data),
+    ]
+}
+
+/// a node of the dag being built: its cid, the file bytes under it and the size of its blocks
+struct Node {
+    cid: Vec<u8>,
+    file_size: u64,
+    dag_size: u64,
+}
+
+/// root cid of the UnixFS file dag `ipfs add` builds
+fn unixfs_root(data: &[u8], version: u8, raw_leaves: bool) -> Vec<u8> {
+    let chunks: Vec<&[u8]> = if data.is_empty() {
+        vec![data]
+    } else {
+        data.chunks(CHUNK_SIZE).collect()
+    };
+    let mut level: Vec<Node> = chunks
+        .into_iter()
+        .map(|chunk| {
+            let block = if raw_leaves {
+                chunk.to_vec()
+            } else {
+                //an empty file has no data field
+                let data = (!chunk.is_empty()).then_some(chunk);
+                pb_node(&[], &unixfs_file(data, chunk.len() as u64, &[]))
+            };
+            Node {
+                cid: cid_bytes(version, if raw_leaves { RAW } else { DAG_PB }, &block),
+                file_size: chunk.len() as u64,
+                dag_size: block.len() as u64,
+            }
+        })
+        .collect();
+    //leaves all at the same depth, every parent full but the last ones
+    while level.len() > 1 {
+        level = level
+            .chunks(MAX_LINKS)
+            .map(|children| {
+                let file_size = children.iter().map(|child| child.file_size).sum();
+                let block_sizes: Vec<u64> = children.iter().map(|child| child.file_size).collect();
+                let block = pb_node(children, &unixfs_file(None, file_size, &block_sizes));
+                Node {
+                    cid: cid_bytes(version, DAG_PB, &block),
+                    file_size,
+                    dag_size: block.len() as u64
+                        + children.iter().map(|child| child.dag_size).sum::<u64>(),
+                }
+            })
+            .collect();
+    }
+    level.remove(0).cid
+}
+
+fn cid_bytes(version: u8, codec: u8, block: &[u8]) -> Vec<u8> {
+    let mut cid = match version {
+        0 => Vec::new(),
+        _ => vec![version, codec],
+    };
+    cid.extend_from_slice(&SHA2_256);
+    cid.extend_from_slice(&Sha256::digest(block));
+    cid
+}
+
+/// UnixFS `Data` message of a file node
+fn unixfs_file(data: Option<&[u8]>, file_size: u64, block_sizes: &[u64]) -> Vec<u8> {
+    let mut message = vec![0x08, 0x02];
+    if let Some(data) = data {
+        bytes_field(&mut message, 0x12, data);
+    }
+    message.push(0x18);
+    varint(&mut message, file_size);
+    for size in block_sizes {
+        message.push(0x20);
+        varint(&mut message, *size);
+    }
+    message
+}
+
+/// dag-pb `PBNode`, links go before the data as in the canonical encoding
+fn pb_node(links: &[Node], data: &[u8]) -> Vec<u8> {
+    let mut node = Vec::new();
+    for link in links {
+        let mut pb_link = Vec::new();
+        bytes_field(&mut pb_link, 0x0a, &link.cid);
+        //empty name
+        bytes_field(&mut pb_link, 0x12, &[]);
+        pb_link.push(0x18);
+        varint(&mut pb_link, link.dag_size);
+        bytes_field(&mut node, 0x12, &pb_link);
+    }
+    bytes_field(&mut node, 0x0a, data);
+    node
+}
+
+fn bytes_field(message: &mut Vec<u8>, tag: u8, bytes: &[u8]) {
+    message.push(tag);
+    varint(message, bytes.len() as u64);
+    message.extend_from_slice(bytes);
+}
+
+fn varint(message: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        message.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    message.push(value as u8);
+}
+
+fn base58_decode(text: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    //big endian, grown as the digits come
+    let mut bytes: Vec<u8> = Vec::new();
+    for char in text.bytes() {
+        let mut carry = ALPHABET.iter().position(|digit| *digit == char)? as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = text.bytes().take_while(|char| *char == b'1').count();
+    Some([vec![0; zeros], bytes].concat())
+}
+
+/// lowercase rfc4648 base32 without padding, the multibase `b` encoding
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut bytes = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for char in text.bytes() {
+        let value = ALPHABET.iter().position(|digit| *digit == char)? as u32;
+        buffer = ((buffer << 5) | value) & 0xfff;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// cids `ipfs add` gives `data`: CIDv0, CIDv1 with raw leaves (the default for CIDv1),
+    /// CIDv1 with dag-pb leaves
+    fn assert_cids(data: &[u8], v0: &str, v1_raw_leaves: &str, v1_dag_pb: &str) {
+        let cids = local_cids(data);
+        assert_eq!(cids[0], decode(v0).unwrap(), "CIDv0");
+        assert_eq!(cids[1], decode(v1_raw_leaves).unwrap(), "CIDv1, raw leaves");
+        assert_eq!(cids[2], decode(v1_dag_pb).unwrap(), "CIDv1, dag-pb leaves");
+    }
+
+    #[test]
+    fn single_chunk() {
+        //as in examples/mock_server.rs
+        assert_cids(
+            b"hello world\n",
+            "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o",
+            "bafkreifjjcie6lypi6ny7amxnfftagclbuxndqonfipmb64f2km2devei4",
+            "bafybeicg2rebjoofv4kbyovkw7af3rpiitvnl6i7ckcywaq6xjcxnc2mby",
+        );
+    }
+
+    #[test]
+    fn empty_file() {
+        assert_cids(
+            b"",
+            "QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH",
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku",
+            "bafybeif7ztnhq65lumvvtr4ekcwd2ifwgm3awq4zfr3srh462rwyinlb4y",
+        );
+    }
+
+    #[test]
+    fn multi_chunk() {
+        //600 KiB, three chunks under a single root. Computed by an independent implementation of the
+        //UnixFS balanced layout, as there's no ipfs node to add it to in the test environment
+        let data: Vec<u8> = (0..600 * 1024).map(|i| (i % 251) as u8).collect();
+        assert_cids(
+            &data,
+            "QmeqHWd7YuJc1yAQiampjeVfMVcK5zS63zD6vHG8qHN9xz",
+            "bafybeib7rtiuapefdrbklxz5ysik3bixpon5h6cdrim7jhclqfyaz2t5cu",
+            "bafybeigif3zel37dubnz6ogyrhjwjtb57wogajmc44dfiunjiyzk5bulj4",
+        );
+        //the whole content as a single raw block
+        assert_eq!(
+            local_cids(&data)[3],
+            decode("bafkreid5w7pafacsqljtgkssiegnlbxzu67dx5dhak4l5enyo6nyle5eo4").unwrap()
+        );
+    }
+
+    #[test]
+    fn decodes_v0_and_v1_of_the_same_node() {
+        let v0 = decode("QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH").unwrap();
+        let v1 = decode("bafybeif7ztnhq65lumvvtr4ekcwd2ifwgm3awq4zfr3srh462rwyinlb4y").unwrap();
+        assert_eq!(v1[..2], [1, DAG_PB]);
+        assert_eq!(v1[2..], v0[..]);
+    }
+
+    #[test]
+    fn rejects_other_encodings() {
+        //base58btc CIDv1 (`z...`), a cid with a path, not a cid
+        assert_eq!(
+            decode("zdj7WWeQ43G6JJvLWQWZpyHuAMq6uYWRjkBXFad11vE2LHhQ7"),
+            None
+        );
+        assert_eq!(
+            decode("QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o/cover.png"),
+            None
+        );
+        assert_eq!(decode("not a cid"), None);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::receipt::sha256_hex;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Claims on the assets of a collection split across workers (machines, or processes with different
+/// work dirs), kept in a directory they share, e.g. a network volume: the first worker to create
+/// `<claims dir>/<asset>` downloads the asset, the others skip it
+pub struct Claims {
+    dir: PathBuf,
+    worker: String,
+}
+
+/// An asset claimed by this worker, released (so another worker can take it) when dropped
+/// unless it was kept
+pub struct Claim {
+    path: PathBuf,
+    kept: bool,
+}
+
+impl Claims {
+    pub fn new(dir: &Path, worker: &str) -> io::Result<Claims> {
+        fs::create_dir_all(dir)?;
+        Ok(Claims {
+            dir: dir.to_path_buf(),
+            worker: worker.to_owned(),
+        })
+    }
+
+    /// claims an asset, `Err` with the worker holding it if it's claimed by another one.
+    /// Claims made by this worker in previous runs are claimed again
+    pub fn claim(&self, asset: &str) -> io::Result<Result<Claim, String>> {
+        let path = self.dir.join(asset);
+        //the claim is written apart and linked in place: linking is atomic, also on network file
+        //systems, and a crash never leaves a claim without its holder
+        let written = self.dir.join(format!(
+            ".{}.{}.tmp",
+            asset,
+            &sha256_hex(self.worker.as_bytes())[..16]
+        ));
+        fs::write(&written, &self.worker)?;
+        let linked = fs::hard_link(&written, &path);
+        fs::remove_file(&written)?;
+        match linked {
+            Ok(()) => Ok(Ok(Claim { path, kept: false })),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                let holder = fs::read_to_string(&path)?;
+                if holder == self.worker {
+                    Ok(Ok(Claim { path, kept: false }))
+                } else {
+                    Ok(Err(holder))
+                }
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Claim {
+    /// the asset was handled, it stays claimed
+    pub fn keep(mut self) {
+        self.kept = true;
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if !self.kept {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("book_cli-claims-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn claimed(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn claim_is_released_on_drop() {
+        let dir = test_dir("release");
+        let claims = Claims::new(&dir, "worker1").unwrap();
+        let claim = claims.claim("asset1").unwrap().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("asset1")).unwrap(), "worker1");
+        //no temp file left behind
+        assert_eq!(claimed(&dir), ["asset1"]);
+
+        drop(claim);
+        assert!(claimed(&dir).is_empty());
+        //so another worker can take it
+        let others = Claims::new(&dir, "worker2").unwrap();
+        assert!(others.claim("asset1").unwrap().is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn kept_claim_is_reclaimed_by_its_worker_only() {
+        let dir = test_dir("keep");
+        Claims::new(&dir, "worker1")
+            .unwrap()
+            .claim("asset1")
+            .unwrap()
+            .unwrap()
+            .keep();
+        assert_eq!(claimed(&dir), ["asset1"]);
+
+        //the same worker in a later run
+        let claims = Claims::new(&dir, "worker1").unwrap();
+        let reclaimed = claims.claim("asset1").unwrap().unwrap();
+
+        let others = Claims::new(&dir, "worker2").unwrap();
+        match others.claim("asset1").unwrap() {
+            Ok(_) => panic!("claimed by two workers"),
+            Err(holder) => assert_eq!(holder, "worker1"),
+        }
+        //refusing it doesn't release it
+        assert_eq!(fs::read_to_string(dir.join("asset1")).unwrap(), "worker1");
+        reclaimed.keep();
+        assert_eq!(claimed(&dir), ["asset1"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use serde::Deserialize;
+use std::{collections::HashSet, error::Error, fs, path::PathBuf};
+
+pub const BOOK_IO_COLLECTIONS_URL: &str = "https://api.book.io/api/v0/collections";
+
+/// Where the catalog of known collections (policy ids) comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollectionSource {
+    /// an http endpoint answering like the book.io collections api
+    Url(String),
+    /// a static json file, either in the book.io response format or a plain list of policy ids
+    File(PathBuf),
+    /// no catalog, any policy id is accepted
+    None,
+}
+
+impl Default for CollectionSource {
+    fn default() -> Self {
+        CollectionSource::Url(BOOK_IO_COLLECTIONS_URL.to_owned())
+    }
+}
+
+/// Source to follow when the catalog and the chain disagree on a collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trust {
+    /// collections with assets onchain are downloaded even if the catalog doesn't list them
+    Chain,
+    /// collections listed in the catalog must have assets onchain, otherwise the chain
+    /// (e.g. the network of the Blockfrost project) is taken as misconfigured
+    Catalog,
+}
+
+impl Trust {
+    pub fn parse(trust: &str) -> Option<Trust> {
+        match trust {
+            "chain" => Some(Trust::Chain),
+            "catalog" => Some(Trust::Catalog),
+            _ => None,
+        }
+    }
+}
+
+impl CollectionSource {
+    /// parses `none`, an http(s) url or a file path
+    pub fn parse(source: &str) -> CollectionSource {
+        if source == "none" {
+            CollectionSource::None
+        } else if source.starts_with("http://") || source.starts_with("https://") {
+            CollectionSource::Url(source.to_owned())
+        } else {
+            CollectionSource::File(PathBuf::from(source))
+        }
+    }
+
+    /// Fetchs the full list of policies from the catalog, `None` if there's no catalog to check against
+    pub async fn collections(&self) -> Result<Option<HashSet<String>>, Box<dyn Error>> {
+        match self {
+            CollectionSource::Url(url) => Ok(Some(fetch_collections(url).await?)),
+            CollectionSource::File(path) => {
+                let parsed_data: CatalogFile = serde_json::from_slice(&fs::read(path)?)?;
+                Ok(Some(parsed_data.policy_ids()))
+            }
+            CollectionSource::None => Ok(None),
+        }
+    }
+}
+
+//structs representing book.io json response
+#[derive(Debug, Deserialize)]
+struct CollectionsResponse {
+    #[serde(rename = "type")]
+    data_type: String,
+    data: Vec<DataEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DataEntry {
+    collection_id: String,
+    description: String,
+    blockchain: String,
+    network: String,
+}
+
+impl CollectionsResponse {
+    fn policy_ids(&self) -> HashSet<String> {
+        self.data.iter().map(|de| de.collection_id.clone()).collect()
+    }
+}
+
+// accepted formats for static catalog files
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CatalogFile {
+    BookIo(CollectionsResponse),
+    PolicyIds(Vec<String>),
+}
+
+impl CatalogFile {
+    fn policy_ids(self) -> HashSet<String> {
+        match self {
+            CatalogFile::BookIo(response) => response.policy_ids(),
+            CatalogFile::PolicyIds(ids) => ids.into_iter().collect(),
+        }
+    }
+}
+
+/// Fetchs the full list of policies from a book.io compatible api
+async fn fetch_collections(url: &str) -> Result<HashSet<String>, reqwest::Error> {
+    let client = reqwest::Client::new();
+
+    // Send the GET request
+    let response = client.get(url).send().await?;
+
+    // Check if the request was successful
+    if response.status().is_success() {
+        // Parse the JSON response into your struct
+        let parsed_data: CollectionsResponse = response.json().await?;
+        Ok(parsed_data.policy_ids())
+    } else {
+        Ok(HashSet::new())
+    }
+}
@@ -0,0 +1,43 @@
+use serde_json::Value;
+
+/// keys whose values are secrets wherever they are in the configuration
+const SECRET_KEYS: [&str; 4] = [
+    "project_id",
+    "access_key_id",
+    "secret_access_key",
+    "session_token",
+];
+/// tables whose values are all secrets (e.g. gateway `Authorization` headers)
+const SECRET_TABLES: [&str; 1] = ["headers"];
+
+pub const REDACTED: &str = "<redacted>";
+
+/// The configuration (`.blockfrost.toml` and environment) as json, with the secrets redacted so it
+/// can be shown or logged
+pub fn redacted(configurations: &toml::Value) -> Value {
+    let mut configurations = serde_json::to_value(configurations).unwrap_or(Value::Null);
+    redact(&mut configurations);
+    configurations
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(entries) => {
+            for (key, value) in entries.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) {
+                    *value = Value::from(REDACTED);
+                } else if let (true, Value::Object(secrets)) =
+                    (SECRET_TABLES.contains(&key.as_str()), &mut *value)
+                {
+                    for secret in secrets.values_mut() {
+                        *secret = Value::from(REDACTED);
+                    }
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
@@ -0,0 +1,394 @@
+use crate::{
+    chaos::{self, Chaos, Fault},
+    gateway_auth::GatewayAuth,
+};
+use bytes::{Bytes, BytesMut};
+use futures::{
+    future::{BoxFuture, Shared},
+    Future, FutureExt,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH},
+    Response, StatusCode,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::time::{self, Instant};
+use tokio_retry::strategy::{jitter, ExponentialBackoff};
+use tokio_retry::RetryIf;
+
+/// Delays to wait before retrying a failed download, one per retry.
+/// Implemented for closures returning the delays, e.g. `|| iter::repeat(Duration::ZERO).take(3)`;
+/// applications with their own retry framework can use [`NoRetry`] and retry the fetcher calls themselves
+pub trait RetryPolicy: Send + Sync {
+    fn delays(&self) -> Box<dyn Iterator<Item = Duration> + Send>;
+}
+
+impl<F, I> RetryPolicy for F
+where
+    F: Fn() -> I + Send + Sync,
+    I: Iterator<Item = Duration> + Send + 'static,
+{
+    fn delays(&self) -> Box<dyn Iterator<Item = Duration> + Send> {
+        Box::new(self())
+    }
+}
+
+/// Exponential backoff with jitter, the default policy (from 10ms, 3 retries)
+#[derive(Debug, Clone)]
+pub struct ExponentialRetry {
+    pub base_millis: u64,
+    pub retries: usize,
+}
+
+impl Default for ExponentialRetry {
+    fn default() -> Self {
+        ExponentialRetry {
+            base_millis: 10,
+            retries: 3,
+        }
+    }
+}
+
+impl RetryPolicy for ExponentialRetry {
+    fn delays(&self) -> Box<dyn Iterator<Item = Duration> + Send> {
+        Box::new(
+            ExponentialBackoff::from_millis(self.base_millis)
+                .map(jitter) // add jitter to delays
+                .take(self.retries),
+        )
+    }
+}
+
+/// Fails on the first error
+#[derive(Debug, Clone, Default)]
+pub struct NoRetry;
+
+impl RetryPolicy for NoRetry {
+    fn delays(&self) -> Box<dyn Iterator<Item = Duration> + Send> {
+        Box::new(std::iter::empty())
+    }
+}
+
+/// Minimum throughput of the transfers from a gateway, set in the configuration for the gateways that
+/// drip-feed bytes and stall: transfers receiving less than `bytes_per_second` on average over
+/// `window_seconds` are aborted (and retried), while slow ones keeping it up go on
+/// ```toml
+/// [[min_throughput]]
+/// gateway = "https://ipfs.io/ipfs/"
+/// bytes_per_second = 10240
+/// window_seconds = 30
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct MinThroughput {
+    /// gateway url, as given to the fetcher
+    pub gateway: String,
+    pub bytes_per_second: u64,
+    #[serde(default = "default_window_seconds")]
+    pub window_seconds: u64,
+}
+
+impl MinThroughput {
+    /// the window the throughput is checked on, at least one second
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.window_seconds.max(1))
+    }
+}
+
+fn default_window_seconds() -> u64 {
+    30
+}
+
+/// Health of the transfers from a gateway: what its failed attempts cost
+#[derive(Debug, Default)]
+pub struct TransferStats {
+    failed_bytes: AtomicU64,
+    stalled: AtomicU32,
+}
+
+impl TransferStats {
+    /// bytes received by the attempts that failed (e.g. truncated bodies), whether they were retried or not
+    pub fn failed_bytes(&self) -> u64 {
+        self.failed_bytes.load(Ordering::Relaxed)
+    }
+
+    /// attempts aborted for going below the minimum throughput
+    pub fn stalled(&self) -> u32 {
+        self.stalled.load(Ordering::Relaxed)
+    }
+}
+
+/// Failed download, after retrying
+#[derive(Debug)]
+pub enum DownloadError {
+    Http(reqwest::Error),
+    /// the body is shorter (or longer) than the response `Content-Length`
+    Truncated {
+        expected: u64,
+        actual: usize,
+    },
+    /// the download this request was waiting for failed
+    InFlight(Arc<DownloadError>),
+    /// the request couldn't be authenticated (e.g. missing credentials)
+    Auth(String),
+    /// the transfer went below the minimum throughput, `received` bytes in
+    Stalled {
+        bytes_per_second: u64,
+        received: usize,
+    },
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DownloadError::Http(err) => err.fmt(f),
+            DownloadError::Truncated { expected, actual } => write!(
+                f,
+                "incomplete body, {} bytes received out of {}",
+                actual, expected
+            ),
+            DownloadError::InFlight(err) => err.fmt(f),
+            DownloadError::Auth(err) => write!(f, "gateway authentication: {}", err),
+            DownloadError::Stalled {
+                bytes_per_second,
+                received,
+            } => write!(
+                f,
+                "transfer stalled at {} bytes/s, {} bytes received",
+                bytes_per_second, received
+            ),
+        }
+    }
+}
+
+impl Error for DownloadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DownloadError::Http(err) => Some(err),
+            DownloadError::Truncated { .. }
+            | DownloadError::Auth(_)
+            | DownloadError::Stalled { .. } => None,
+            DownloadError::InFlight(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl DownloadError {
+    /// status of the gateway response, if it was an error status
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            DownloadError::Http(err) => err.status(),
+            DownloadError::InFlight(err) => err.status(),
+            _ => None,
+        }
+    }
+
+    /// the gateway refused the request (e.g. 404 for a cid it can't find, 403), retrying won't help.
+    /// 429 and 5xx statuses are transient
+    pub fn is_permanent(&self) -> bool {
+        self.status().is_some_and(|status| {
+            status.is_client_error()
+                && status != StatusCode::TOO_MANY_REQUESTS
+                && status != StatusCode::REQUEST_TIMEOUT
+        })
+    }
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(err: reqwest::Error) -> Self {
+        DownloadError::Http(err)
+    }
+}
+
+type SharedDownload = Shared<BoxFuture<'static, Result<(HeaderMap, Bytes), Arc<DownloadError>>>>;
+
+/// Downloads in progress by key (e.g. cid): concurrent requests for the same key result in a single
+/// fetch, the later ones awaiting the first one's result
+#[derive(Default)]
+pub struct SingleFlight {
+    inflight: Arc<Mutex<HashMap<String, SharedDownload>>>,
+}
+
+impl SingleFlight {
+    /// runs `download` unless there's one in progress for `key`, then waits for that one instead
+    pub async fn run<F>(&self, key: &str, download: F) -> Result<(HeaderMap, Bytes), DownloadError>
+    where
+        F: Future<Output = Result<(HeaderMap, Bytes), DownloadError>> + Send + 'static,
+    {
+        let shared = {
+            let mut inflight = self.inflight.lock().unwrap_or_else(|err| err.into_inner());
+            match inflight.get(key) {
+                Some(shared) => shared.clone(),
+                None => {
+                    //taken out of the in progress ones when it finishes, not when the request that
+                    //started it is cancelled: the requests still waiting keep driving it
+                    let finished = (Arc::clone(&self.inflight), key.to_owned());
+                    let shared = download
+                        .map(move |result| {
+                            let (inflight, key) = finished;
+                            let mut inflight =
+                                inflight.lock().unwrap_or_else(|err| err.into_inner());
+                            inflight.remove(&key);
+                            result.map_err(Arc::new)
+                        })
+                        .boxed()
+                        .shared();
+                    inflight.insert(key.to_owned(), shared.clone());
+                    shared
+                }
+            }
+        };
+        shared
+            .await
+            .map_err(|err| Arc::try_unwrap(err).unwrap_or_else(DownloadError::InFlight))
+    }
+}
+
+/// Downloads a binary file from an url retrying as the policy says, along with the response headers.
+/// Transient error statuses (e.g. 429) and incomplete bodies are retried as well, not the permanent ones (e.g. 404).
+/// Private gateways get the requests authenticated (and signed again on each retry) by `auth`,
+/// transfers going below `min_throughput` are aborted. The failed attempts are tallied in `stats`
+pub async fn download_binary(
+    url: &str,
+    retry_policy: &dyn RetryPolicy,
+    chaos: Option<&Chaos>,
+    auth: Option<&GatewayAuth>,
+    min_throughput: Option<&MinThroughput>,
+    stats: &TransferStats,
+) -> Result<(HeaderMap, Bytes), DownloadError> {
+    RetryIf::start(
+        retry_policy.delays(),
+        || attempt(url, chaos, auth, min_throughput, stats),
+        |err: &DownloadError| !err.is_permanent(),
+    )
+    .await
+}
+
+async fn attempt(
+    url: &str,
+    chaos: Option<&Chaos>,
+    auth: Option<&GatewayAuth>,
+    min_throughput: Option<&MinThroughput>,
+    stats: &TransferStats,
+) -> Result<(HeaderMap, Bytes), DownloadError> {
+    let fault = chaos.and_then(|chaos| chaos.gateway_fault());
+    match fault {
+        Some(Fault::GatewayTimeout) => {
+            return Err(chaos::status_error(StatusCode::GATEWAY_TIMEOUT).into())
+        }
+        Some(Fault::TooManyRequests) => {
+            return Err(chaos::status_error(StatusCode::TOO_MANY_REQUESTS).into())
+        }
+        _ => {}
+    }
+
+    let mut request = reqwest::Client::new().get(url);
+    if let Some(auth) = auth {
+        //later headers replace earlier ones, e.g. the signature an `Authorization` header
+        let mut headers = HeaderMap::new();
+        for (name, value) in auth.headers(url).map_err(DownloadError::Auth)? {
+            let name =
+                HeaderName::try_from(name).map_err(|err| DownloadError::Auth(err.to_string()))?;
+            let value =
+                HeaderValue::try_from(value).map_err(|err| DownloadError::Auth(err.to_string()))?;
+            headers.insert(name, value);
+        }
+        request = request.headers(headers);
+    }
+    let (headers, mut content) = match min_throughput {
+        //waiting for the response counts in the first window, a gateway may stall before answering
+        Some(min_throughput) => {
+            let window_end = Instant::now() + min_throughput.window();
+            let response = match time::timeout_at(window_end, request.send()).await {
+                Ok(response) => response?.error_for_status()?,
+                Err(_) => return Err(stalled(stats, 0, 0)),
+            };
+            let headers = response.headers().clone();
+            let content = read_watched(response, min_throughput, window_end, stats).await?;
+            (headers, content)
+        }
+        None => {
+            let response = request.send().await?.error_for_status()?;
+            (response.headers().clone(), response.bytes().await?)
+        }
+    };
+    //fails the attempt even when there's no `Content-Length` to check the body against
+    if fault == Some(Fault::TruncatedBody) {
+        let expected = content.len() as u64;
+        content.truncate(content.len() / 2);
+        return Err(truncated(stats, expected, content.len()));
+    }
+
+    let content_length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse::<u64>().ok());
+    match content_length {
+        Some(expected) if expected != content.len() as u64 => {
+            Err(truncated(stats, expected, content.len()))
+        }
+        _ => Ok((headers, content)),
+    }
+}
+
+/// the error of an incomplete body, tallying the bytes received for nothing
+fn truncated(stats: &TransferStats, expected: u64, actual: usize) -> DownloadError {
+    stats
+        .failed_bytes
+        .fetch_add(actual as u64, Ordering::Relaxed);
+    DownloadError::Truncated { expected, actual }
+}
+
+/// the attempt went below the minimum throughput, the bytes received count as failed
+fn stalled(stats: &TransferStats, bytes_per_second: u64, received: usize) -> DownloadError {
+    stats.stalled.fetch_add(1, Ordering::Relaxed);
+    stats
+        .failed_bytes
+        .fetch_add(received as u64, Ordering::Relaxed);
+    DownloadError::Stalled {
+        bytes_per_second,
+        received,
+    }
+}
+
+/// reads the body checking its throughput on each window, a pure read timeout would either kill
+/// good slow transfers or let the stalled ones hang
+async fn read_watched(
+    mut response: Response,
+    min_throughput: &MinThroughput,
+    mut window_end: Instant,
+    stats: &TransferStats,
+) -> Result<Bytes, DownloadError> {
+    let window_seconds = min_throughput.window_seconds.max(1);
+    let window = min_throughput.window();
+    let mut content = BytesMut::new();
+    let mut window_bytes: u64 = 0;
+    loop {
+        //times out when the window is over, checked below
+        if let Ok(chunk) = time::timeout_at(window_end, response.chunk()).await {
+            match chunk? {
+                Some(chunk) => {
+                    window_bytes += chunk.len() as u64;
+                    content.extend_from_slice(&chunk);
+                }
+                None => return Ok(content.freeze()),
+            }
+        }
+        if Instant::now() >= window_end {
+            let bytes_per_second = window_bytes / window_seconds;
+            if bytes_per_second < min_throughput.bytes_per_second {
+                return Err(stalled(stats, bytes_per_second, content.len()));
+            }
+            window_end += window;
+            window_bytes = 0;
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+/// Cover cids the publisher expects for its assets, to cross-check against the onchain metadata
+/// and the downloaded content
+#[derive(Debug, Default)]
+pub struct ExpectedCids {
+    cids: HashMap<String, String>,
+}
+
+impl ExpectedCids {
+    /// loads a csv file of `asset,cid` lines (an optional header and `#` comments are skipped)
+    pub fn load(path: &Path) -> Result<ExpectedCids, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        let mut cids = HashMap::new();
+        //the header, if any, is the first line that isn't blank or a comment
+        let mut first = true;
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let is_first = std::mem::replace(&mut first, false);
+            let fields: Vec<&str> = line
+                .split(',')
+                .map(|field| field.trim().trim_matches('"'))
+                .collect();
+            match fields[..] {
+                [asset, _] if is_first && asset.eq_ignore_ascii_case("asset") => {}
+                [asset, cid] => {
+                    //cids may come as ipfs urls
+                    let cid = cid.strip_prefix("ipfs://").unwrap_or(cid);
+                    cids.insert(asset.to_owned(), cid.to_owned());
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid line {} in {:#?}, expected `asset,cid`",
+                        number + 1,
+                        path
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(ExpectedCids { cids })
+    }
+
+    pub fn get(&self, asset: &str) -> Option<&str> {
+        self.cids.get(asset).map(|cid| cid.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn csv_file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "book_cli-expected-{}-{}.csv",
+            name,
+            std::process::id()
+        ));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn load(name: &str, content: &str) -> Result<ExpectedCids, String> {
+        let path = csv_file(name, content);
+        let expected = ExpectedCids::load(&path).map_err(|err| err.to_string());
+        fs::remove_file(&path).unwrap();
+        expected
+    }
+
+    #[test]
+    fn header_after_the_comments_is_skipped() {
+        let expected = load(
+            "header",
+            "# exported from the catalog\n\nAsset,CID\nasset1,QmCover1\n",
+        )
+        .unwrap();
+        assert_eq!(expected.get("asset1"), Some("QmCover1"));
+        assert_eq!(expected.get("Asset"), None);
+    }
+
+    #[test]
+    fn header_is_only_the_first_line() {
+        //an asset named as the header column further down is an asset
+        let expected = load("asset-named-asset", "asset1,QmCover1\nasset,QmCover2\n").unwrap();
+        assert_eq!(expected.get("asset"), Some("QmCover2"));
+    }
+
+    #[test]
+    fn quoted_fields_and_ipfs_urls() {
+        let expected = load(
+            "quoted",
+            "\"asset1\", \"ipfs://QmCover1\"\nasset2,ipfs://QmCover2\n  asset3 ,QmCover3  \n",
+        )
+        .unwrap();
+        assert_eq!(expected.get("asset1"), Some("QmCover1"));
+        assert_eq!(expected.get("asset2"), Some("QmCover2"));
+        assert_eq!(expected.get("asset3"), Some("QmCover3"));
+    }
+
+    #[test]
+    fn malformed_line_is_an_error() {
+        let err = load("malformed", "asset,cid\nasset1,QmCover1\nasset2\n").unwrap_err();
+        assert!(err.starts_with("invalid line 3 in"), "{}", err);
+        let err = load("extra-field", "asset1,QmCover1,image/png\n").unwrap_err();
+        assert!(err.starts_with("invalid line 1 in"), "{}", err);
+    }
+}
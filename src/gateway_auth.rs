@@ -0,0 +1,331 @@
+use crate::{
+    receipt::{hex, sha256_hex},
+    timestamp,
+};
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, env};
+
+/// Authentication for a private gateway, set in the configuration for each gateway that needs it
+/// ```toml
+/// [[gateway_auth]]
+/// gateway = "https://gateway.example.com/ipfs/"
+/// headers = { Authorization = "Bearer <token>" }
+///
+/// [[gateway_auth]]
+/// gateway = "https://<id>.execute-api.us-east-1.amazonaws.com/ipfs/"
+/// sigv4 = { region = "us-east-1", service = "execute-api" }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct GatewayAuth {
+    /// gateway url, as given to the fetcher
+    pub gateway: String,
+    /// headers added to every request
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// AWS Signature Version 4 signing of the requests
+    pub sigv4: Option<SigV4>,
+}
+
+/// AWS SigV4 request signing, the credentials are taken from the `AWS_ACCESS_KEY_ID`,
+/// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN` environment variables when not configured
+#[derive(Debug, Clone, Deserialize)]
+pub struct SigV4 {
+    pub region: String,
+    #[serde(default = "default_service")]
+    pub service: String,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub session_token: Option<String>,
+}
+
+fn default_service() -> String {
+    "execute-api".to_owned()
+}
+
+/// sha2-256 of an empty payload, the one of GET requests
+const EMPTY_PAYLOAD_HASH: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+impl GatewayAuth {
+    /// the headers to send with a GET request of `url`
+    pub fn headers(&self, url: &str) -> Result<Vec<(String, String)>, String> {
+        let mut headers: Vec<(String, String)> = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        if let Some(sigv4) = &self.sigv4 {
+            let url = Url::parse(url).map_err(|err| err.to_string())?;
+            let amz_date = timestamp::to_compact(timestamp::unix_now());
+            headers.extend(sigv4.sign(&url, &amz_date)?);
+        }
+        Ok(headers)
+    }
+}
+
+impl SigV4 {
+    /// signing headers for a GET request of `url` at `amz_date` (`YYYYMMDDTHHMMSSZ`)
+    pub fn sign(&self, url: &Url, amz_date: &str) -> Result<Vec<(String, String)>, String> {
+        let access_key_id = credential(&self.access_key_id, "AWS_ACCESS_KEY_ID")
+            .ok_or("missing AWS access key id")?;
+        let secret_access_key = credential(&self.secret_access_key, "AWS_SECRET_ACCESS_KEY")
+            .ok_or("missing AWS secret access key")?;
+        let session_token = credential(&self.session_token, "AWS_SESSION_TOKEN");
+
+        let mut host = url.host_str().ok_or("gateway url without host")?.to_owned();
+        if let Some(port) = url.port() {
+            host = format!("{}:{}", host, port);
+        }
+        //sorted by name, as they are signed
+        let mut headers = vec![
+            ("host".to_owned(), host),
+            (
+                "x-amz-content-sha256".to_owned(),
+                EMPTY_PAYLOAD_HASH.to_owned(),
+            ),
+            ("x-amz-date".to_owned(), amz_date.to_owned()),
+        ];
+        if let Some(session_token) = session_token {
+            headers.push(("x-amz-security-token".to_owned(), session_token));
+        }
+
+        let authorization =
+            self.authorization(url, amz_date, &headers, &access_key_id, &secret_access_key);
+        //the http client sets the host itself
+        headers.retain(|(name, _)| name != "host");
+        headers.push(("authorization".to_owned(), authorization));
+        Ok(headers)
+    }
+
+    /// `Authorization` header value for a GET request with the (lowercase, sorted) `headers`
+    pub(crate) fn authorization(
+        &self,
+        url: &Url,
+        amz_date: &str,
+        headers: &[(String, String)],
+        access_key_id: &str,
+        secret_access_key: &str,
+    ) -> String {
+        let mut query: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(name, value)| (uri_encode(&name), uri_encode(&value)))
+            .collect();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("&");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let payload_hash = headers
+            .iter()
+            .find(|(name, _)| name == "x-amz-content-sha256")
+            .map(|(_, value)| value.as_str())
+            .unwrap_or(EMPTY_PAYLOAD_HASH);
+        let canonical_request = format!(
+            "GET\n{}\n{}\n{}\n{}\n{}",
+            url.path(),
+            canonical_query,
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256_hex(canonical_request.as_bytes())
+        );
+
+        let key = hmac_sha256(
+            format!("AWS4{}", secret_access_key).as_bytes(),
+            date.as_bytes(),
+        );
+        let key = hmac_sha256(&key, self.region.as_bytes());
+        let key = hmac_sha256(&key, self.service.as_bytes());
+        let key = hmac_sha256(&key, b"aws4_request");
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            access_key_id, scope, signed_headers, signature
+        )
+    }
+}
+
+fn credential(configured: &Option<String>, var: &str) -> Option<String> {
+    configured.clone().or_else(|| env::var(var).ok())
+}
+
+/// percent-encodes all but the unreserved characters, as SigV4 requires
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|byte| byte ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|byte| byte ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //RFC 4231 test cases 1, 2 and 6 (key longer than the block size)
+    #[test]
+    fn hmac_sha256_known_answers() {
+        assert_eq!(
+            hex(&hmac_sha256(&[0x0b; 20], b"Hi There")),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    const ACCESS_KEY_ID: &str = "AKIDEXAMPLE";
+    const SECRET_ACCESS_KEY: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+
+    fn sigv4(service: &str) -> SigV4 {
+        SigV4 {
+            region: "us-east-1".to_owned(),
+            service: service.to_owned(),
+            access_key_id: Some(ACCESS_KEY_ID.to_owned()),
+            secret_access_key: Some(SECRET_ACCESS_KEY.to_owned()),
+            session_token: None,
+        }
+    }
+
+    fn headers(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    //AWS SigV4 test suite `get-vanilla`
+    #[test]
+    fn sigv4_get_vanilla() {
+        let url = Url::parse("https://example.amazonaws.com/").unwrap();
+        let headers = headers(&[
+            ("host", "example.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ]);
+        assert_eq!(
+            sigv4("service").authorization(
+                &url,
+                "20150830T123600Z",
+                &headers,
+                ACCESS_KEY_ID,
+                SECRET_ACCESS_KEY
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    //AWS SigV4 test suite `get-vanilla-query-order-key-case`, the query is signed sorted
+    #[test]
+    fn sigv4_get_vanilla_query_order() {
+        let url = Url::parse("https://example.amazonaws.com/?Param2=value2&Param1=value1").unwrap();
+        let headers = headers(&[
+            ("host", "example.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ]);
+        assert_eq!(
+            sigv4("service").authorization(
+                &url,
+                "20150830T123600Z",
+                &headers,
+                ACCESS_KEY_ID,
+                SECRET_ACCESS_KEY
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"
+        );
+    }
+
+    //the IAM `ListUsers` example of the AWS signing documentation
+    #[test]
+    fn sigv4_iam_list_users() {
+        let url =
+            Url::parse("https://iam.amazonaws.com/?Action=ListUsers&Version=2010-05-08").unwrap();
+        let headers = headers(&[
+            (
+                "content-type",
+                "application/x-www-form-urlencoded; charset=utf-8",
+            ),
+            ("host", "iam.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ]);
+        assert_eq!(
+            sigv4("iam").authorization(
+                &url,
+                "20150830T123600Z",
+                &headers,
+                ACCESS_KEY_ID,
+                SECRET_ACCESS_KEY
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, SignedHeaders=content-type;host;x-amz-date, Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+
+    #[test]
+    fn sigv4_signs_content_hash_and_session_token() {
+        let mut sigv4 = sigv4("execute-api");
+        sigv4.session_token = Some("<token>".to_owned());
+        let url = Url::parse("https://gateway.example.com:8443/ipfs/QmCover").unwrap();
+        let headers = sigv4.sign(&url, "20150830T123600Z").unwrap();
+        let names: Vec<&str> = headers.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "x-amz-content-sha256",
+                "x-amz-date",
+                "x-amz-security-token",
+                "authorization"
+            ]
+        );
+        assert!(headers[3]
+            .1
+            .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-security-token,"));
+    }
+}
@@ -0,0 +1,743 @@
+use crate::summary::RunCounts;
+use std::{env, path::Path};
+
+/// Language of the human readable output, machine readable output (progress events, json) is always english
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Lang {
+    #[default]
+    En,
+    Es,
+    Pt,
+}
+
+impl Lang {
+    /// from a language tag or locale name (`es`, `pt-BR`, `es_AR.UTF-8`, ...)
+    pub fn parse(tag: &str) -> Option<Lang> {
+        let language = tag.split(['_', '-', '.', '@']).next()?;
+        match language.to_ascii_lowercase().as_str() {
+            "en" | "c" | "posix" => Some(Lang::En),
+            "es" => Some(Lang::Es),
+            "pt" => Some(Lang::Pt),
+            _ => None,
+        }
+    }
+
+    /// the requested language if any, otherwise the one of the locale (`LC_ALL`, `LC_MESSAGES`, `LANG`),
+    /// falling back to english
+    pub fn detect(requested: Option<&str>) -> Result<Lang, String> {
+        if let Some(requested) = requested {
+            return Lang::parse(requested).ok_or(format!("unsupported language {:#?}", requested));
+        }
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty());
+        Ok(locale
+            .and_then(|locale| Lang::parse(&locale))
+            .unwrap_or_default())
+    }
+}
+
+/// User facing messages of the cli
+#[derive(Debug, Clone)]
+pub enum Message<'a> {
+    MissingPolicyId,
+    NotAllowedInLockedMode {
+        policy_id: &'a str,
+    },
+    InvalidPolicyId {
+        policy_id: &'a str,
+    },
+    /// the catalog lists the collection but it has no assets onchain
+    CatalogOnly {
+        policy_id: &'a str,
+    },
+    /// the collection has assets onchain but the catalog doesn't list it
+    ChainOnly {
+        policy_id: &'a str,
+        downloading: bool,
+    },
+    RunningGroup {
+        name: &'a str,
+    },
+    FoundCover {
+        name: &'a str,
+    },
+    AlreadyDownloaded {
+        asset: &'a str,
+    },
+    UpToDate {
+        asset: &'a str,
+    },
+    CoverChanged {
+        asset: &'a str,
+        previous_cid: &'a str,
+        cid: &'a str,
+        archived: &'a Path,
+    },
+    PolicyViolations {
+        asset: &'a str,
+        violations: &'a [String],
+    },
+    AliasOf {
+        asset: &'a str,
+        of: &'a str,
+    },
+    SameAsExisting {
+        path: &'a str,
+        asset: &'a str,
+    },
+    NoCover {
+        asset: &'a str,
+    },
+    DownloadFailed {
+        asset: &'a str,
+        cid: &'a str,
+        error: &'a str,
+    },
+    NoCoverExpected {
+        asset: &'a str,
+        expected: &'a str,
+    },
+    ExpectedCidSameContent {
+        asset: &'a str,
+        onchain: &'a str,
+        expected: &'a str,
+    },
+    ExpectedCidDifferentContent {
+        asset: &'a str,
+        onchain: &'a str,
+        expected: &'a str,
+    },
+    RecordedCidMismatch {
+        asset: &'a str,
+        recorded: &'a str,
+        expected: &'a str,
+    },
+    ExpectedCidUnverifiable {
+        asset: &'a str,
+        onchain: &'a str,
+        expected: &'a str,
+        error: &'a str,
+    },
+    UnknownFormat {
+        file: &'a Path,
+    },
+    Renamed {
+        from: &'a Path,
+        to: &'a Path,
+    },
+    Adopted {
+        asset: &'a str,
+        file: &'a Path,
+    },
+    NotAdopted {
+        file: &'a Path,
+    },
+    AdoptTargetExists {
+        asset: &'a str,
+        file: &'a Path,
+    },
+    Refetched {
+        asset: &'a str,
+        file: &'a str,
+    },
+    UnmatchedCid {
+        cid: &'a str,
+    },
+    ClaimedByOtherWorker {
+        asset: &'a str,
+        worker: &'a str,
+    },
+    StateAt {
+        at: &'a str,
+        covers: usize,
+        cids: usize,
+    },
+    FsckMissingFile {
+        asset: &'a str,
+    },
+    FsckHashMismatch {
+        asset: &'a str,
+        file: &'a Path,
+    },
+    FsckDanglingAlias {
+        asset: &'a str,
+        of: &'a str,
+    },
+    FsckMissingVersion {
+        asset: &'a str,
+        file: &'a str,
+    },
+    FsckUntracked {
+        file: &'a Path,
+    },
+    FsckDone {
+        problems: usize,
+        repaired: bool,
+    },
+    VerifyOk {
+        asset: &'a str,
+    },
+    VerifyMissing {
+        asset: &'a str,
+    },
+    VerifyMismatch {
+        asset: &'a str,
+        expected: &'a str,
+        actual: &'a str,
+    },
+    VerifyUnreadable {
+        asset: &'a str,
+        error: &'a str,
+    },
+    ProgressFeedStopped {
+        error: &'a str,
+    },
+    Usage,
+    Summary(RunCounts),
+}
+
+impl Message<'_> {
+    pub fn text(&self, lang: Lang) -> String {
+        use Lang::*;
+        use Message::*;
+        match (self, lang) {
+            (MissingPolicyId, En) => "Missing policy id".to_owned(),
+            (MissingPolicyId, Es) => "Falta el policy id".to_owned(),
+            (MissingPolicyId, Pt) => "Falta o policy id".to_owned(),
+
+            (NotAllowedInLockedMode { policy_id }, En) => {
+                format!("policy id {:#?} is not allowed in locked mode", policy_id)
+            }
+            (NotAllowedInLockedMode { policy_id }, Es) => {
+                format!("el policy id {:#?} no está permitido en modo bloqueado", policy_id)
+            }
+            (NotAllowedInLockedMode { policy_id }, Pt) => {
+                format!("o policy id {:#?} não é permitido no modo bloqueado", policy_id)
+            }
+
+            (InvalidPolicyId { policy_id }, En) => format!("invalid policy id {:#?}", policy_id),
+            (InvalidPolicyId { policy_id }, Es) => format!("policy id inválido {:#?}", policy_id),
+            (InvalidPolicyId { policy_id }, Pt) => format!("policy id inválido {:#?}", policy_id),
+
+            (CatalogOnly { policy_id }, En) => format!(
+                "Policy id {:#?} is in the collection catalog but has no assets onchain: the catalog may list a collection of another network, or the Blockfrost project is for the wrong one",
+                policy_id
+            ),
+            (CatalogOnly { policy_id }, Es) => format!(
+                "El policy id {:#?} está en el catálogo de colecciones pero no tiene assets en la cadena: el catálogo puede listar una colección de otra red, o el proyecto de Blockfrost es de la red equivocada",
+                policy_id
+            ),
+            (CatalogOnly { policy_id }, Pt) => format!(
+                "O policy id {:#?} está no catálogo de coleções mas não tem assets na cadeia: o catálogo pode listar uma coleção de outra rede, ou o projeto do Blockfrost é da rede errada",
+                policy_id
+            ),
+
+            (ChainOnly { policy_id, downloading }, En) => format!(
+                "Policy id {:#?} has assets onchain but isn't in the collection catalog, which may be outdated: {}",
+                policy_id,
+                if *downloading { "downloading it as the chain is trusted" } else { "use --trust=chain to download it anyway" }
+            ),
+            (ChainOnly { policy_id, downloading }, Es) => format!(
+                "El policy id {:#?} tiene assets en la cadena pero no está en el catálogo de colecciones, que puede estar desactualizado: {}",
+                policy_id,
+                if *downloading { "se descarga porque se confía en la cadena" } else { "use --trust=chain para descargarlo de todos modos" }
+            ),
+            (ChainOnly { policy_id, downloading }, Pt) => format!(
+                "O policy id {:#?} tem assets na cadeia mas não está no catálogo de coleções, que pode estar desatualizado: {}",
+                policy_id,
+                if *downloading { "baixando porque a cadeia é confiável" } else { "use --trust=chain para baixá-lo mesmo assim" }
+            ),
+
+            (RunningGroup { name }, En) => format!("Running group {:#?}", name),
+            (RunningGroup { name }, Es) => format!("Ejecutando el grupo {:#?}", name),
+            (RunningGroup { name }, Pt) => format!("Executando o grupo {:#?}", name),
+
+            (FoundCover { name }, En) => format!("Found high-res cover for {:#?}", name),
+            (FoundCover { name }, Es) => format!("Portada en alta resolución encontrada para {:#?}", name),
+            (FoundCover { name }, Pt) => format!("Capa em alta resolução encontrada para {:#?}", name),
+
+            (AlreadyDownloaded { asset }, En) => format!("Asset {:#?} already downloaded", asset),
+            (AlreadyDownloaded { asset }, Es) => format!("El asset {:#?} ya fue descargado", asset),
+            (AlreadyDownloaded { asset }, Pt) => format!("O asset {:#?} já foi baixado", asset),
+
+            (UpToDate { asset }, En) => {
+                format!("Asset {:#?} already downloaded and up to date", asset)
+            }
+            (UpToDate { asset }, Es) => {
+                format!("El asset {:#?} ya fue descargado y está actualizado", asset)
+            }
+            (UpToDate { asset }, Pt) => {
+                format!("O asset {:#?} já foi baixado e está atualizado", asset)
+            }
+
+            (CoverChanged { asset, previous_cid, cid, archived }, En) => format!(
+                "High-res cover for asset {:#?} changed from {:#?} to {:#?}, previous one kept as {:#?}",
+                asset, previous_cid, cid, archived
+            ),
+            (CoverChanged { asset, previous_cid, cid, archived }, Es) => format!(
+                "La portada en alta resolución del asset {:#?} cambió de {:#?} a {:#?}, la anterior se guardó como {:#?}",
+                asset, previous_cid, cid, archived
+            ),
+            (CoverChanged { asset, previous_cid, cid, archived }, Pt) => format!(
+                "A capa em alta resolução do asset {:#?} mudou de {:#?} para {:#?}, a anterior foi guardada como {:#?}",
+                asset, previous_cid, cid, archived
+            ),
+
+            (PolicyViolations { asset, violations }, En) => format!(
+                "High-res cover for asset {:#?} doesn't conform to the cover policy: {}",
+                asset,
+                violations.join(", ")
+            ),
+            (PolicyViolations { asset, violations }, Es) => format!(
+                "La portada en alta resolución del asset {:#?} no cumple la política de portadas: {}",
+                asset,
+                violations.join(", ")
+            ),
+            (PolicyViolations { asset, violations }, Pt) => format!(
+                "A capa em alta resolução do asset {:#?} não cumpre a política de capas: {}",
+                asset,
+                violations.join(", ")
+            ),
+
+            (AliasOf { asset, of }, En) => format!(
+                "High-res cover for asset {:#?} is the same as asset {:#?}'s, recorded as its alias",
+                asset, of
+            ),
+            (AliasOf { asset, of }, Es) => format!(
+                "La portada en alta resolución del asset {:#?} es la misma que la del asset {:#?}, registrado como su alias",
+                asset, of
+            ),
+            (AliasOf { asset, of }, Pt) => format!(
+                "A capa em alta resolução do asset {:#?} é a mesma do asset {:#?}, registrado como seu alias",
+                asset, of
+            ),
+
+            (SameAsExisting { path, asset }, En) => format!(
+                "High-res cover {:#?} for asset {:#?} is the same as existing one",
+                path, asset
+            ),
+            (SameAsExisting { path, asset }, Es) => format!(
+                "La portada en alta resolución {:#?} del asset {:#?} es igual a una existente",
+                path, asset
+            ),
+            (SameAsExisting { path, asset }, Pt) => format!(
+                "A capa em alta resolução {:#?} do asset {:#?} é igual a uma existente",
+                path, asset
+            ),
+
+            (NoCover { asset }, En) => format!("Asset without high-res cover image: {:#?}", asset),
+            (NoCover { asset }, Es) => {
+                format!("Asset sin imagen de portada en alta resolución: {:#?}", asset)
+            }
+            (NoCover { asset }, Pt) => {
+                format!("Asset sem imagem de capa em alta resolução: {:#?}", asset)
+            }
+
+            (DownloadFailed { asset, cid, error }, En) => format!(
+                "High-res cover {:#?} for asset {:#?} can't be downloaded: {}",
+                cid, asset, error
+            ),
+            (DownloadFailed { asset, cid, error }, Es) => format!(
+                "La portada en alta resolución {:#?} del asset {:#?} no se puede descargar: {}",
+                cid, asset, error
+            ),
+            (DownloadFailed { asset, cid, error }, Pt) => format!(
+                "A capa em alta resolução {:#?} do asset {:#?} não pode ser baixada: {}",
+                cid, asset, error
+            ),
+
+            (NoCoverExpected { asset, expected }, En) => format!(
+                "Asset {:#?} has no high-res cover onchain, the publisher expects {:#?}",
+                asset, expected
+            ),
+            (NoCoverExpected { asset, expected }, Es) => format!(
+                "El asset {:#?} no tiene portada en alta resolución onchain, el editor espera {:#?}",
+                asset, expected
+            ),
+            (NoCoverExpected { asset, expected }, Pt) => format!(
+                "O asset {:#?} não tem capa em alta resolução onchain, a editora espera {:#?}",
+                asset, expected
+            ),
+
+            (ExpectedCidSameContent { asset, onchain, expected }, En) => format!(
+                "Asset {:#?} onchain cid {:#?} differs from the expected {:#?}, but the content is the same",
+                asset, onchain, expected
+            ),
+            (ExpectedCidSameContent { asset, onchain, expected }, Es) => format!(
+                "El cid onchain {:#?} del asset {:#?} difiere del esperado {:#?}, pero el contenido es el mismo",
+                onchain, asset, expected
+            ),
+            (ExpectedCidSameContent { asset, onchain, expected }, Pt) => format!(
+                "O cid onchain {:#?} do asset {:#?} difere do esperado {:#?}, mas o conteúdo é o mesmo",
+                onchain, asset, expected
+            ),
+
+            (ExpectedCidDifferentContent { asset, onchain, expected }, En) => format!(
+                "Asset {:#?} onchain cid {:#?} differs from the expected {:#?}, and so does the content",
+                asset, onchain, expected
+            ),
+            (ExpectedCidDifferentContent { asset, onchain, expected }, Es) => format!(
+                "El cid onchain {:#?} del asset {:#?} difiere del esperado {:#?}, y también el contenido",
+                onchain, asset, expected
+            ),
+            (ExpectedCidDifferentContent { asset, onchain, expected }, Pt) => format!(
+                "O cid onchain {:#?} do asset {:#?} difere do esperado {:#?}, e o conteúdo também",
+                onchain, asset, expected
+            ),
+
+            (RecordedCidMismatch { asset, recorded, expected }, En) => format!(
+                "Asset {:#?} recorded cid {:#?} differs from the expected {:#?}",
+                asset, recorded, expected
+            ),
+            (RecordedCidMismatch { asset, recorded, expected }, Es) => format!(
+                "El cid registrado {:#?} del asset {:#?} difiere del esperado {:#?}",
+                recorded, asset, expected
+            ),
+            (RecordedCidMismatch { asset, recorded, expected }, Pt) => format!(
+                "O cid registrado {:#?} do asset {:#?} difere do esperado {:#?}",
+                recorded, asset, expected
+            ),
+
+            (ExpectedCidUnverifiable { asset, onchain, expected, error }, En) => format!(
+                "Asset {:#?} onchain cid {:#?} differs from the expected {:#?}, whose content can't be downloaded to compare: {}",
+                asset, onchain, expected, error
+            ),
+            (ExpectedCidUnverifiable { asset, onchain, expected, error }, Es) => format!(
+                "El cid onchain {:#?} del asset {:#?} difiere del esperado {:#?}, cuyo contenido no se puede descargar para comparar: {}",
+                onchain, asset, expected, error
+            ),
+            (ExpectedCidUnverifiable { asset, onchain, expected, error }, Pt) => format!(
+                "O cid onchain {:#?} do asset {:#?} difere do esperado {:#?}, cujo conteúdo não pode ser baixado para comparar: {}",
+                onchain, asset, expected, error
+            ),
+
+            (UnknownFormat { file }, En) => {
+                format!("Unknown image format for {:#?}, left as is", file)
+            }
+            (UnknownFormat { file }, Es) => {
+                format!("Formato de imagen desconocido para {:#?}, se deja como está", file)
+            }
+            (UnknownFormat { file }, Pt) => {
+                format!("Formato de imagem desconhecido para {:#?}, mantido como está", file)
+            }
+
+            (Renamed { from, to }, En) => format!("Renamed {:#?} to {:#?}", from, to),
+            (Renamed { from, to }, Es) => format!("{:#?} renombrado a {:#?}", from, to),
+            (Renamed { from, to }, Pt) => format!("{:#?} renomeado para {:#?}", from, to),
+
+            (Adopted { asset, file }, En) => format!("Adopted {:#?} as asset {:#?}", file, asset),
+            (Adopted { asset, file }, Es) => format!("{:#?} adoptado como asset {:#?}", file, asset),
+            (Adopted { asset, file }, Pt) => format!("{:#?} adotado como asset {:#?}", file, asset),
+
+            (NotAdopted { file }, En) => format!("{:#?} doesn't match any cover of the collection", file),
+            (NotAdopted { file }, Es) => format!("{:#?} no coincide con ninguna portada de la colección", file),
+            (NotAdopted { file }, Pt) => format!("{:#?} não corresponde a nenhuma capa da coleção", file),
+
+            (AdoptTargetExists { asset, file }, En) => format!("{:#?} already exists with other content, asset {:#?} not adopted", file, asset),
+            (AdoptTargetExists { asset, file }, Es) => format!("{:#?} ya existe con otro contenido, el asset {:#?} no fue adoptado", file, asset),
+            (AdoptTargetExists { asset, file }, Pt) => format!("{:#?} já existe com outro conteúdo, o asset {:#?} não foi adotado", file, asset),
+
+            (Refetched { asset, file }, En) => format!("Downloaded asset {:#?} again to {:#?}", asset, file),
+            (Refetched { asset, file }, Es) => format!("Asset {:#?} descargado de nuevo en {:#?}", asset, file),
+            (Refetched { asset, file }, Pt) => format!("Asset {:#?} baixado novamente em {:#?}", asset, file),
+
+            (UnmatchedCid { cid }, En) => format!("No cover recorded with cid {:#?}, nothing to download again", cid),
+            (UnmatchedCid { cid }, Es) => format!("No hay portada registrada con el cid {:#?}, nada que descargar de nuevo", cid),
+            (UnmatchedCid { cid }, Pt) => format!("Nenhuma capa registrada com o cid {:#?}, nada a baixar novamente", cid),
+
+            (VerifyOk { asset }, En) => format!("Asset {:#?} ok", asset),
+            (VerifyOk { asset }, Es) => format!("Asset {:#?} correcto", asset),
+            (VerifyOk { asset }, Pt) => format!("Asset {:#?} correto", asset),
+
+            (VerifyMissing { asset }, En) => format!("Asset {:#?} missing", asset),
+            (VerifyMissing { asset }, Es) => format!("Falta el asset {:#?}", asset),
+            (VerifyMissing { asset }, Pt) => format!("Falta o asset {:#?}", asset),
+
+            (VerifyMismatch { asset, expected, actual }, En) => format!(
+                "Asset {:#?} doesn't match its cid content (expected sha256 {}, found {})",
+                asset, expected, actual
+            ),
+            (VerifyMismatch { asset, expected, actual }, Es) => format!(
+                "El asset {:#?} no coincide con el contenido de su cid (sha256 esperado {}, encontrado {})",
+                asset, expected, actual
+            ),
+            (VerifyMismatch { asset, expected, actual }, Pt) => format!(
+                "O asset {:#?} não corresponde ao conteúdo do seu cid (sha256 esperado {}, encontrado {})",
+                asset, expected, actual
+            ),
+
+            (VerifyUnreadable { asset, error }, En) => {
+                format!("Asset {:#?} can't be read: {}", asset, error)
+            }
+            (VerifyUnreadable { asset, error }, Es) => {
+                format!("No se puede leer el asset {:#?}: {}", asset, error)
+            }
+            (VerifyUnreadable { asset, error }, Pt) => {
+                format!("Não é possível ler o asset {:#?}: {}", asset, error)
+            }
+
+            (ClaimedByOtherWorker { asset, worker }, En) => {
+                format!("Asset {:#?} claimed by worker {:#?}", asset, worker)
+            }
+            (ClaimedByOtherWorker { asset, worker }, Es) => {
+                format!("El asset {:#?} fue reservado por el worker {:#?}", asset, worker)
+            }
+            (ClaimedByOtherWorker { asset, worker }, Pt) => {
+                format!("O asset {:#?} foi reservado pelo worker {:#?}", asset, worker)
+            }
+
+            (StateAt { at, covers, cids }, En) => {
+                format!("Covers at {}: {} ({} distinct cids)", at, covers, cids)
+            }
+            (StateAt { at, covers, cids }, Es) => {
+                format!("Portadas al {}: {} ({} cids distintos)", at, covers, cids)
+            }
+            (StateAt { at, covers, cids }, Pt) => {
+                format!("Capas em {}: {} ({} cids distintos)", at, covers, cids)
+            }
+
+            (FsckMissingFile { asset }, En) => format!("Asset {:#?} is recorded but its cover file is missing", asset),
+            (FsckMissingFile { asset }, Es) => format!("El asset {:#?} está registrado pero falta su archivo de portada", asset),
+            (FsckMissingFile { asset }, Pt) => format!("O asset {:#?} está registrado mas falta seu arquivo de capa", asset),
+
+            (FsckHashMismatch { asset, file }, En) => format!(
+                "Cover file {:#?} of asset {:#?} differs from the recorded content, `refetch` can download it again",
+                file, asset
+            ),
+            (FsckHashMismatch { asset, file }, Es) => format!(
+                "El archivo de portada {:#?} del asset {:#?} difiere del contenido registrado, `refetch` puede descargarlo de nuevo",
+                file, asset
+            ),
+            (FsckHashMismatch { asset, file }, Pt) => format!(
+                "O arquivo de capa {:#?} do asset {:#?} difere do conteúdo registrado, `refetch` pode baixá-lo novamente",
+                file, asset
+            ),
+
+            (FsckDanglingAlias { asset, of }, En) => format!(
+                "Asset {:#?} is recorded as an alias of {:#?}, which doesn't have its cover",
+                asset, of
+            ),
+            (FsckDanglingAlias { asset, of }, Es) => format!(
+                "El asset {:#?} está registrado como alias de {:#?}, que no tiene su portada",
+                asset, of
+            ),
+            (FsckDanglingAlias { asset, of }, Pt) => format!(
+                "O asset {:#?} está registrado como alias de {:#?}, que não tem sua capa",
+                asset, of
+            ),
+
+            (FsckMissingVersion { asset, file }, En) => format!("Superseded cover {:#?} of asset {:#?} is missing", file, asset),
+            (FsckMissingVersion { asset, file }, Es) => format!("Falta la portada reemplazada {:#?} del asset {:#?}", file, asset),
+            (FsckMissingVersion { asset, file }, Pt) => format!("Falta a capa substituída {:#?} do asset {:#?}", file, asset),
+
+            (FsckUntracked { file }, En) => format!("Cover file {:#?} isn't recorded in the manifest, `adopt` can register it", file),
+            (FsckUntracked { file }, Es) => format!("El archivo de portada {:#?} no está registrado en el manifiesto, `adopt` puede registrarlo", file),
+            (FsckUntracked { file }, Pt) => format!("O arquivo de capa {:#?} não está registrado no manifesto, `adopt` pode registrá-lo", file),
+
+            (FsckDone { problems, repaired: false }, En) => format!("{} problems found, `--repair` fixes the manifest ones", problems),
+            (FsckDone { problems, repaired: false }, Es) => format!("{} problemas encontrados, `--repair` corrige los del manifiesto", problems),
+            (FsckDone { problems, repaired: false }, Pt) => format!("{} problemas encontrados, `--repair` corrige os do manifesto", problems),
+            (FsckDone { problems, repaired: true }, En) => format!("{} problems found, manifest repaired and compacted", problems),
+            (FsckDone { problems, repaired: true }, Es) => format!("{} problemas encontrados, manifiesto reparado y compactado", problems),
+            (FsckDone { problems, repaired: true }, Pt) => format!("{} problemas encontrados, manifesto reparado e compactado", problems),
+
+            (ProgressFeedStopped { error }, En) => format!("Progress feed stopped: {}", error),
+            (ProgressFeedStopped { error }, Es) => format!("Se detuvo el envío del progreso: {}", error),
+            (ProgressFeedStopped { error }, Pt) => format!("O envio do progresso parou: {}", error),
+
+            (Usage, lang) => usage(lang),
+            (Summary(counts), lang) => summary(counts, lang),
+        }
+    }
+}
+
+/// command line help: the commands as typed, then what the arguments, flags and commands do
+fn usage(lang: Lang) -> String {
+    let commands = [
+        "book_cli <policy_id> <work_dir>? <total_files>? <ipfs_gateway>? [--locked] [--receipts] [--refresh] [--collections=<source>] [--manifest-layout=single|sharded] [--progress-socket=<path>] [--expected-cids=<csv file>] [--lang=en|es|pt] [--min-quantity=<n>] [--claims-dir=<shared dir> --worker=<name>] [--metrics-file=<path>] [--trust=chain|catalog]",
+        "book_cli verify <policy_id> <work_dir>? <ipfs_gateway>?",
+        "book_cli sample <policy_id>",
+        "book_cli metadata <asset_id> [--raw|--parsed]",
+        "book_cli run-jobs <jobs_file>",
+        "book_cli fix-extensions <work_dir>",
+        "book_cli state at <date> <work_dir>?",
+        "book_cli state fsck <work_dir>? [--repair]",
+        "book_cli adopt <policy_id> <dir> <work_dir>?",
+        "book_cli config show [<flags>]",
+        "book_cli refetch <work_dir> <ipfs_gateway>? --cids=<file>|--assets=<file> [--receipts]",
+    ];
+    let descriptions = match lang {
+        Lang::En => [
+            "policy_id (mandatory): policy id of the asset",
+            "work_dir (optional): directory where to store the files (default: current directory)",
+            "total_files (optional): maximum number of files to download (default: 10)",
+            "ipfs http gateway (optional): url of the ipfs gateway (default: https://ipfs.io/ipfs/)",
+            "--locked: only download the policies listed in the configuration `allowed_policies`",
+            "--receipts: write a `<asset>.receipt.json` next to each downloaded cover",
+            "--refresh: download again the covers whose cid changed since they were downloaded",
+            "--collections=<source>: collection catalog, an url, a json file or `none` (default: book.io)",
+            "--manifest-layout=single|sharded: store the manifest as one file or sharded by asset hash (default: keep the current one)",
+            "--progress-socket=<path>: send progress events (NDJSON) to a unix socket or named pipe",
+            "--expected-cids=<csv file>: cross-check the onchain cids and downloaded content with the publisher's `asset,cid` list",
+            "--min-quantity=<n>: skip the assets with less copies in circulation (default: 1)",
+            "--claims-dir=<shared dir>: split the collection with other workers claiming the assets in a shared directory, `--worker` (required) names this one, the same from run to run",
+            "--trust=chain|catalog: source to follow when the catalog and the chain disagree on a collection, download it if it has assets onchain (chain) or fail if a listed one has none (catalog)",
+            "--metrics-file=<path>: write the run counters as a Prometheus text snapshot, e.g. for node_exporter's textfile collector",
+            "--lang=en|es|pt: language of the messages (default: the locale's one, or english)",
+            "verify: check the downloaded covers against the content of their onchain cid",
+            "sample: download 3 covers to a temp directory and print their paths",
+            "metadata: print the asset onchain metadata, as is (--raw) or as the cover is picked from it (--parsed, default)",
+            "run-jobs: download the groups of collections described in a toml jobs file",
+            "fix-extensions: add the extension of their format to covers saved without one",
+            "adopt: register the covers of a collection found in a directory (matched by content to their onchain cid) instead of downloading them",
+            "refetch: download again the covers listed (one per line) by cid or asset id, even if their files exist, e.g. to repair corrupted ones",
+            "config show: print the configuration the flags given would run with, as json and with the secrets redacted",
+            "state fsck: cross-check the manifest and the files of a work dir, `--repair` forgets the missing covers and compacts the manifest",
+            "state at: list the covers the work dir had at a date (`2024-01-01`) or time (`2024-01-01T10:00:00Z`, UTC)",
+        ],
+        Lang::Es => [
+            "policy_id (obligatorio): policy id del asset",
+            "work_dir (opcional): directorio donde guardar los archivos (por defecto: el directorio actual)",
+            "total_files (opcional): cantidad máxima de archivos a descargar (por defecto: 10)",
+            "ipfs http gateway (opcional): url del gateway de ipfs (por defecto: https://ipfs.io/ipfs/)",
+            "--locked: descargar sólo las policies listadas en `allowed_policies` de la configuración",
+            "--receipts: escribir un `<asset>.receipt.json` junto a cada portada descargada",
+            "--refresh: descargar de nuevo las portadas cuyo cid cambió desde que se descargaron",
+            "--collections=<source>: catálogo de colecciones, una url, un archivo json o `none` (por defecto: book.io)",
+            "--manifest-layout=single|sharded: guardar el manifiesto en un archivo o dividido por hash del asset (por defecto: mantener el actual)",
+            "--progress-socket=<path>: enviar los eventos de progreso (NDJSON) a un socket unix o named pipe",
+            "--expected-cids=<csv file>: verificar los cids onchain y el contenido descargado contra la lista `asset,cid` del editor",
+            "--min-quantity=<n>: omitir los assets con menos copias en circulación (por defecto: 1)",
+            "--claims-dir=<shared dir>: repartir la colección con otros workers reservando los assets en un directorio compartido, `--worker` (obligatorio) nombra a este, el mismo en cada ejecución",
+            "--trust=chain|catalog: fuente a seguir cuando el catálogo y la cadena no coinciden sobre una colección, descargarla si tiene assets onchain (chain) o fallar si una listada no tiene (catalog)",
+            "--metrics-file=<path>: escribir los contadores de la ejecución como un snapshot de texto Prometheus, p. ej. para el textfile collector de node_exporter",
+            "--lang=en|es|pt: idioma de los mensajes (por defecto: el del locale, o inglés)",
+            "verify: verificar las portadas descargadas contra el contenido de su cid onchain",
+            "sample: descargar 3 portadas a un directorio temporal e imprimir sus rutas",
+            "metadata: imprimir la metadata onchain del asset, tal cual (--raw) o según se elige la portada (--parsed, por defecto)",
+            "run-jobs: descargar los grupos de colecciones descriptos en un archivo de jobs toml",
+            "fix-extensions: agregar la extensión de su formato a las portadas guardadas sin ella",
+            "adopt: registrar las portadas de una colección encontradas en un directorio (asociadas por contenido a su cid onchain) en lugar de descargarlas",
+            "refetch: descargar de nuevo las portadas listadas (una por línea) por cid o asset id, aunque existan sus archivos, p. ej. para reparar las corruptas",
+            "config show: imprimir la configuración con la que correría con los flags dados, como json y con los secretos ocultos",
+            "state fsck: verificar el manifiesto contra los archivos de un work dir, `--repair` olvida las portadas faltantes y compacta el manifiesto",
+            "state at: listar las portadas que tenía el work dir en una fecha (`2024-01-01`) u hora (`2024-01-01T10:00:00Z`, UTC)",
+        ],
+        Lang::Pt => [
+            "policy_id (obrigatório): policy id do asset",
+            "work_dir (opcional): diretório onde salvar os arquivos (padrão: o diretório atual)",
+            "total_files (opcional): número máximo de arquivos a baixar (padrão: 10)",
+            "ipfs http gateway (opcional): url do gateway ipfs (padrão: https://ipfs.io/ipfs/)",
+            "--locked: baixar apenas as policies listadas em `allowed_policies` da configuração",
+            "--receipts: escrever um `<asset>.receipt.json` ao lado de cada capa baixada",
+            "--refresh: baixar novamente as capas cujo cid mudou desde que foram baixadas",
+            "--collections=<source>: catálogo de coleções, uma url, um arquivo json ou `none` (padrão: book.io)",
+            "--manifest-layout=single|sharded: salvar o manifesto em um arquivo ou dividido por hash do asset (padrão: manter o atual)",
+            "--progress-socket=<path>: enviar os eventos de progresso (NDJSON) a um socket unix ou named pipe",
+            "--expected-cids=<csv file>: conferir os cids onchain e o conteúdo baixado com a lista `asset,cid` da editora",
+            "--min-quantity=<n>: pular os assets com menos cópias em circulação (padrão: 1)",
+            "--claims-dir=<shared dir>: dividir a coleção com outros workers reservando os assets em um diretório compartilhado, `--worker` (obrigatório) nomeia este, o mesmo em cada execução",
+            "--trust=chain|catalog: fonte a seguir quando o catálogo e a chain discordam sobre uma coleção, baixá-la se tem assets onchain (chain) ou falhar se uma listada não tem (catalog)",
+            "--metrics-file=<path>: escrever os contadores da execução como um snapshot de texto Prometheus, p. ex. para o textfile collector do node_exporter",
+            "--lang=en|es|pt: idioma das mensagens (padrão: o do locale, ou inglês)",
+            "verify: conferir as capas baixadas com o conteúdo do seu cid onchain",
+            "sample: baixar 3 capas para um diretório temporário e imprimir seus caminhos",
+            "metadata: imprimir os metadados onchain do asset, como estão (--raw) ou como a capa é escolhida deles (--parsed, padrão)",
+            "run-jobs: baixar os grupos de coleções descritos em um arquivo de jobs toml",
+            "fix-extensions: adicionar a extensão do seu formato às capas salvas sem ela",
+            "adopt: registrar as capas de uma coleção encontradas em um diretório (associadas pelo conteúdo ao seu cid onchain) em vez de baixá-las",
+            "refetch: baixar novamente as capas listadas (uma por linha) por cid ou asset id, mesmo que seus arquivos existam, p. ex. para reparar as corrompidas",
+            "config show: imprimir a configuração com que rodaria com os flags dados, como json e com os segredos ocultos",
+            "state fsck: conferir o manifesto com os arquivos de um work dir, `--repair` esquece as capas que faltam e compacta o manifesto",
+            "state at: listar as capas que o work dir tinha em uma data (`2024-01-01`) ou hora (`2024-01-01T10:00:00Z`, UTC)",
+        ],
+    };
+    let label = match lang {
+        Lang::En => "Usage:",
+        Lang::Es => "Uso:",
+        Lang::Pt => "Uso:",
+    };
+    let mut text = format!("{} \t{}", label, commands[0]);
+    for line in commands[1..].iter().chain(descriptions.iter()) {
+        text += "\n\t";
+        text += line;
+    }
+    text
+}
+
+/// end of run report, one counter per line
+fn summary(counts: &RunCounts, lang: Lang) -> String {
+    let labels = match lang {
+        Lang::En => [
+            "Summary",
+            "downloaded",
+            "superseded",
+            "already downloaded",
+            "duplicates",
+            "without cover",
+            "expected cid mismatches",
+            "cover policy violations",
+            "claimed by other workers",
+            "failed downloads",
+        ],
+        Lang::Es => [
+            "Resumen",
+            "descargados",
+            "reemplazados",
+            "ya descargados",
+            "duplicados",
+            "sin portada",
+            "cids distintos a los esperados",
+            "violaciones de la política de portadas",
+            "reservados por otros workers",
+            "descargas fallidas",
+        ],
+        Lang::Pt => [
+            "Resumo",
+            "baixados",
+            "substituídos",
+            "já baixados",
+            "duplicados",
+            "sem capa",
+            "cids diferentes dos esperados",
+            "violações da política de capas",
+            "reservados por outros workers",
+            "downloads com falha",
+        ],
+    };
+    let mut text = labels[0].to_owned() + ":";
+    for (label, (_, value)) in labels[1..].iter().zip(counts.outcomes()) {
+        text += &format!("\n\t{}: {}", label, value);
+    }
+    let (useful, wasted, duplicate, failed) = (
+        counts.useful_bytes,
+        counts.wasted_bytes(),
+        counts.duplicate_bytes,
+        counts.failed_bytes,
+    );
+    text += &match lang {
+        Lang::En => format!(
+            "\n\ttransferred: {} bytes useful, {} wasted ({} in duplicates, {} in failed transfers)",
+            useful, wasted, duplicate, failed
+        ),
+        Lang::Es => format!(
+            "\n\ttransferidos: {} bytes útiles, {} desperdiciados ({} en duplicados, {} en transferencias fallidas)",
+            useful, wasted, duplicate, failed
+        ),
+        Lang::Pt => format!(
+            "\n\ttransferidos: {} bytes úteis, {} desperdiçados ({} em duplicados, {} em transferências com falha)",
+            useful, wasted, duplicate, failed
+        ),
+    };
+    text += &match lang {
+        Lang::En => format!("\n\tstalled transfers: {}", counts.stalled_transfers),
+        Lang::Es => format!(
+            "\n\ttransferencias estancadas: {}",
+            counts.stalled_transfers
+        ),
+        Lang::Pt => format!("\n\ttransferências travadas: {}", counts.stalled_transfers),
+    };
+    text
+}
@@ -0,0 +1,243 @@
+/// Image formats covers come in, recognized by their magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Avif,
+    Svg,
+}
+
+impl ImageFormat {
+    pub const ALL: [ImageFormat; 6] = [
+        ImageFormat::Png,
+        ImageFormat::Jpeg,
+        ImageFormat::Gif,
+        ImageFormat::Webp,
+        ImageFormat::Avif,
+        ImageFormat::Svg,
+    ];
+
+    /// detects the format from the first bytes of the content
+    pub fn sniff(data: &[u8]) -> Option<ImageFormat> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(ImageFormat::Jpeg)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+            Some(ImageFormat::Webp)
+        } else if data.get(4..8) == Some(b"ftyp")
+            && matches!(data.get(8..12), Some(b"avif") | Some(b"avis"))
+        {
+            Some(ImageFormat::Avif)
+        } else if is_svg(data) {
+            Some(ImageFormat::Svg)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Avif => "avif",
+            ImageFormat::Svg => "svg",
+        }
+    }
+
+    /// parses a format name as used in configurations (`jpeg` or `jpg` for jpeg)
+    pub fn parse(name: &str) -> Option<ImageFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "jpeg" => Some(ImageFormat::Jpeg),
+            name => ImageFormat::ALL
+                .into_iter()
+                .find(|format| format.extension() == name),
+        }
+    }
+
+    /// width and height from the image header, `None` for unsupported formats (avif, svg) or invalid content
+    pub fn dimensions(self, data: &[u8]) -> Option<(u32, u32)> {
+        match self {
+            ImageFormat::Png => Some((be_u32(data, 16)?, be_u32(data, 20)?)),
+            ImageFormat::Gif => Some((le_u16(data, 6)?, le_u16(data, 8)?)),
+            ImageFormat::Jpeg => jpeg_dimensions(data),
+            ImageFormat::Webp => webp_dimensions(data),
+            ImageFormat::Avif | ImageFormat::Svg => None,
+        }
+    }
+}
+
+// svg is text, look for the root element near the start (after an optional xml declaration)
+fn is_svg(data: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    (head.starts_with("<?xml") || head.starts_with("<svg")) && head.contains("<svg")
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le_u16(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le_u24(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 3)?;
+    Some(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16)
+}
+
+// walks the jpeg segments up to the start of frame, which has the dimensions
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut at = 2;
+    loop {
+        if *data.get(at)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(at + 1)?;
+        match marker {
+            //fill bytes
+            0xff => at += 1,
+            //markers without length
+            0x01 | 0xd0..=0xd9 => at += 2,
+            //start of frame (0xc4, 0xc8 and 0xcc are other segments)
+            0xc0..=0xcf if marker != 0xc4 && marker != 0xc8 && marker != 0xcc => {
+                return Some((be_u16(data, at + 7)?, be_u16(data, at + 5)?));
+            }
+            _ => at += 2 + be_u16(data, at + 2)? as usize,
+        }
+    }
+}
+
+// lossy (VP8), lossless (VP8L) and extended (VP8X) webp headers
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        b"VP8 " => Some((le_u16(data, 26)? & 0x3fff, le_u16(data, 28)? & 0x3fff)),
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+            Some((1 + (bits & 0x3fff), 1 + ((bits >> 14) & 0x3fff)))
+        }
+        b"VP8X" => Some((1 + le_u24(data, 24)?, 1 + le_u24(data, 27)?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data
+    }
+
+    fn webp(chunk: &[u8; 4], header: &[u8]) -> Vec<u8> {
+        let mut data = b"RIFF\0\0\0\0WEBP".to_vec();
+        data.extend_from_slice(chunk);
+        data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        data.extend_from_slice(header);
+        data
+    }
+
+    #[test]
+    fn sniffs_the_magic_bytes() {
+        let cases: [(&[u8], Option<ImageFormat>); 10] = [
+            (&png(1, 1), Some(ImageFormat::Png)),
+            (b"\xff\xd8\xff\xe0", Some(ImageFormat::Jpeg)),
+            (b"GIF87a", Some(ImageFormat::Gif)),
+            (b"GIF89a", Some(ImageFormat::Gif)),
+            (&webp(b"VP8 ", &[]), Some(ImageFormat::Webp)),
+            (b"\0\0\0\x1cftypavif", Some(ImageFormat::Avif)),
+            (b"\0\0\0\x1cftypavis", Some(ImageFormat::Avif)),
+            (
+                b"\xef\xbb\xbf<?xml version=\"1.0\"?>\n<svg>",
+                Some(ImageFormat::Svg),
+            ),
+            (
+                b"  <svg xmlns=\"http://www.w3.org/2000/svg\">",
+                Some(ImageFormat::Svg),
+            ),
+            (b"<?xml version=\"1.0\"?><html>", None),
+        ];
+        for (data, format) in cases {
+            assert_eq!(ImageFormat::sniff(data), format, "{:?}", data);
+        }
+        assert_eq!(ImageFormat::sniff(b"RIFF\0\0\0\0WAVE"), None);
+        assert_eq!(ImageFormat::sniff(b""), None);
+    }
+
+    #[test]
+    fn png_and_gif_dimensions() {
+        assert_eq!(
+            ImageFormat::Png.dimensions(&png(600, 900)),
+            Some((600, 900))
+        );
+        assert_eq!(
+            ImageFormat::Gif.dimensions(b"GIF89a\x58\x02\x84\x03"),
+            Some((600, 900))
+        );
+        //truncated headers
+        assert_eq!(ImageFormat::Png.dimensions(&png(600, 900)[..22]), None);
+        assert_eq!(ImageFormat::Gif.dimensions(b"GIF89a\x58\x02"), None);
+    }
+
+    #[test]
+    fn jpeg_dimensions_after_other_segments() {
+        let mut data = vec![0xff, 0xd8];
+        //APP0, 16 bytes long
+        data.extend_from_slice(&[0xff, 0xe0, 0x00, 0x10]);
+        data.extend_from_slice(b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+        //a huffman table (0xc4, not a start of frame), then a fill byte
+        data.extend_from_slice(&[0xff, 0xc4, 0x00, 0x03, 0x00, 0xff]);
+        //baseline start of frame: precision, height, width
+        data.extend_from_slice(&[0xff, 0xc0, 0x00, 0x11, 0x08, 0x03, 0x84, 0x02, 0x58]);
+        assert_eq!(ImageFormat::Jpeg.dimensions(&data), Some((600, 900)));
+
+        //no start of frame before the end of the data
+        assert_eq!(ImageFormat::Jpeg.dimensions(&data[..24]), None);
+        //not a marker where a segment starts
+        assert_eq!(
+            ImageFormat::Jpeg.dimensions(&[0xff, 0xd8, 0x00, 0xc0]),
+            None
+        );
+    }
+
+    #[test]
+    fn webp_dimensions() {
+        //lossy: frame tag, start code, then 14 bit sizes (the 2 upper bits are the scale)
+        let vp8 = webp(b"VP8 ", b"\0\0\0\x9d\x01\x2a\x58\x42\x84\x03");
+        assert_eq!(ImageFormat::Webp.dimensions(&vp8), Some((600, 900)));
+
+        //lossless: signature, then width - 1 and height - 1 in 14 bits each
+        let bits: u32 = 599 | 899 << 14;
+        let mut header = vec![0x2f];
+        header.extend_from_slice(&bits.to_le_bytes());
+        let vp8l = webp(b"VP8L", &header);
+        assert_eq!(ImageFormat::Webp.dimensions(&vp8l), Some((600, 900)));
+
+        //extended: flags, then width - 1 and height - 1 in 24 bits each
+        let vp8x = webp(b"VP8X", b"\0\0\0\0\x57\x02\0\x83\x03\0");
+        assert_eq!(ImageFormat::Webp.dimensions(&vp8x), Some((600, 900)));
+
+        assert_eq!(ImageFormat::Webp.dimensions(&webp(b"ALPH", &[0; 10])), None);
+        assert_eq!(ImageFormat::Webp.dimensions(&vp8x[..28]), None);
+    }
+
+    #[test]
+    fn no_dimensions_for_avif_and_svg() {
+        assert_eq!(ImageFormat::Avif.dimensions(b"\0\0\0\x1cftypavif"), None);
+        assert_eq!(ImageFormat::Svg.dimensions(b"<svg width=\"600\">"), None);
+    }
+}
@@ -0,0 +1,39 @@
+use serde::Deserialize;
+use std::{error::Error, fs, path::Path};
+
+/// Declarative batch of downloads: named groups of collections, each with its own storage and limits
+/// ```toml
+/// [[group]]
+/// name = "classics"
+/// work_dir = "covers/classics"
+/// total_files = 100
+/// policies = ["<policy id>", "<other policy id>"]
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct JobsFile {
+    #[serde(rename = "group", default)]
+    pub groups: Vec<JobGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobGroup {
+    pub name: String,
+    /// where the group covers are stored
+    pub work_dir: String,
+    /// maximum number of files to download for each collection in the group
+    #[serde(default = "default_total_files")]
+    pub total_files: u32,
+    /// ipfs gateway for the group, the default one if not set
+    pub gateway: Option<String>,
+    pub policies: Vec<String>,
+}
+
+fn default_total_files() -> u32 {
+    10
+}
+
+impl JobsFile {
+    pub fn load(path: &Path) -> Result<JobsFile, Box<dyn Error>> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+}
@@ -0,0 +1,217 @@
+//! Download and verify the high-res covers of book.io assets from the ipfs network
+
+use blockfrost::{AssetPolicy, BlockFrostApi};
+use bytes::Bytes;
+use reqwest::header::HeaderMap;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex, Weak},
+};
+
+mod cache;
+pub mod chaos;
+pub mod cid;
+pub mod claims;
+pub mod collections;
+pub mod config;
+pub mod download;
+pub mod expected;
+pub mod gateway_auth;
+pub mod i18n;
+pub mod image;
+pub mod jobs;
+pub mod manifest;
+pub mod metadata;
+pub mod metrics;
+#[cfg(feature = "test-util")]
+pub mod mock;
+pub mod onchain;
+pub mod policy;
+pub mod progress;
+pub mod receipt;
+pub mod storage;
+pub mod summary;
+pub mod timestamp;
+mod verify;
+
+pub use cache::CoverHandle;
+pub use chaos::Chaos;
+pub use claims::Claims;
+pub use collections::{CollectionSource, Trust};
+pub use download::{
+    DownloadError, ExponentialRetry, MinThroughput, NoRetry, RetryPolicy, SingleFlight,
+    TransferStats,
+};
+pub use expected::ExpectedCids;
+pub use gateway_auth::GatewayAuth;
+pub use i18n::{Lang, Message};
+pub use image::ImageFormat;
+pub use manifest::{Manifest, ManifestLayout};
+pub use metadata::{BookFile, BookMetadata};
+pub use onchain::Onchain;
+pub use policy::CoverPolicy;
+pub use progress::{ProgressEvent, ProgressSink};
+pub use summary::{RunCounts, RunSummary};
+pub use verify::VerifyEvent;
+
+/// High-res cover found in an asset's onchain metadata
+#[derive(Debug, Clone)]
+pub struct Cover {
+    pub name: String,
+    pub path: String,
+    pub cid: String,
+    /// media type declared for the file, if any
+    pub media_type: Option<String>,
+}
+
+/// Fetches asset covers using Blockfrost for the onchain metadata and an ipfs http gateway for the content
+pub struct CoverFetcher {
+    api: BlockFrostApi,
+    //where the assets and their metadata are read from, Blockfrost unless replaced
+    onchain: Arc<dyn Onchain>,
+    ipfs_gateway: String,
+    retry_policy: Arc<dyn RetryPolicy>,
+    chaos: Option<Arc<Chaos>>,
+    gateway_auth: Option<Arc<GatewayAuth>>,
+    cache_dir: Option<PathBuf>,
+    min_throughput: Option<Arc<MinThroughput>>,
+    transfers: Arc<TransferStats>,
+    //cid downloads in progress
+    downloads: SingleFlight,
+    //assets being fetched by `get_cover`
+    inflight: Mutex<HashMap<String, Weak<tokio::sync::Mutex<()>>>>,
+}
+
+impl CoverFetcher {
+    pub fn new(api: BlockFrostApi, ipfs_gateway: &str) -> CoverFetcher {
+        CoverFetcher {
+            onchain: Arc::new(api.clone()),
+            api,
+            ipfs_gateway: ipfs_gateway.to_owned(),
+            retry_policy: Arc::new(ExponentialRetry::default()),
+            chaos: None,
+            gateway_auth: None,
+            cache_dir: None,
+            min_throughput: None,
+            transfers: Arc::default(),
+            downloads: SingleFlight::default(),
+            inflight: Mutex::default(),
+        }
+    }
+
+    /// replaces the default retry policy of the gateway downloads
+    pub fn with_retry_policy(mut self, retry_policy: impl RetryPolicy + 'static) -> CoverFetcher {
+        self.retry_policy = Arc::new(retry_policy);
+        self
+    }
+
+    /// authenticates the requests to a private gateway
+    pub fn with_gateway_auth(mut self, gateway_auth: GatewayAuth) -> CoverFetcher {
+        self.gateway_auth = Some(Arc::new(gateway_auth));
+        self
+    }
+
+    /// injects faults in the Blockfrost calls and gateway downloads, for testing
+    pub fn with_chaos(mut self, chaos: Chaos) -> CoverFetcher {
+        self.chaos = Some(Arc::new(chaos));
+        self
+    }
+
+    /// reads the assets and their metadata from somewhere else than Blockfrost, e.g. the mock server
+    pub fn with_onchain(mut self, onchain: impl Onchain + 'static) -> CoverFetcher {
+        self.onchain = Arc::new(onchain);
+        self
+    }
+
+    pub fn api(&self) -> &BlockFrostApi {
+        &self.api
+    }
+
+    /// gateway url for a cid
+    pub fn cover_url(&self, cid: &str) -> String {
+        self.ipfs_gateway.to_owned() + cid
+    }
+
+    /// aborts the gateway transfers going below a minimum throughput
+    pub fn with_min_throughput(mut self, min_throughput: MinThroughput) -> CoverFetcher {
+        self.min_throughput = Some(Arc::new(min_throughput));
+        self
+    }
+
+    /// health of the gateway transfers so far: bytes lost to failed attempts and stalled transfers
+    pub fn transfers(&self) -> &TransferStats {
+        &self.transfers
+    }
+
+    /// the assets minted under a policy id
+    pub async fn assets(&self, policy_id: &str) -> blockfrost::Result<Vec<AssetPolicy>> {
+        self.blockfrost_fault()?;
+        self.onchain.assets_policy_by_id(policy_id).await
+    }
+
+    /// fetches the asset's onchain metadata and extracts its high-res cover, if any
+    pub async fn cover(&self, asset: &str) -> blockfrost::Result<Option<Cover>> {
+        self.blockfrost_fault()?;
+        let asset_details = self.onchain.assets_by_id(asset).await?;
+        Ok(get_high_res_cover(asset_details))
+    }
+
+    /// the Blockfrost error injected by chaos, if it strikes
+    fn blockfrost_fault(&self) -> blockfrost::Result<()> {
+        match &self.chaos {
+            Some(chaos) if chaos.blockfrost_fault().is_some() => {
+                let err = chaos::status_error(reqwest::StatusCode::SERVICE_UNAVAILABLE);
+                Err(blockfrost::Error::Reqwest(err))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// downloads the cover content from the ipfs gateway, concurrent downloads of the same cid are
+    /// made once
+    pub async fn download(&self, cid: &str) -> Result<(HeaderMap, Bytes), DownloadError> {
+        let url = self.cover_url(cid);
+        let retry_policy = self.retry_policy.clone();
+        let chaos = self.chaos.clone();
+        let gateway_auth = self.gateway_auth.clone();
+        let min_throughput = self.min_throughput.clone();
+        let transfers = self.transfers.clone();
+        self.downloads
+            .run(cid, async move {
+                download::download_binary(
+                    &url,
+                    retry_policy.as_ref(),
+                    chaos.as_deref(),
+                    gateway_auth.as_deref(),
+                    min_throughput.as_deref(),
+                    &transfers,
+                )
+                .await
+            })
+            .await
+    }
+}
+
+///Extracts the high-res cover (the first file, if it has a `src`) from the asset's onchain metadata
+pub fn get_high_res_cover(asset_details: blockfrost::AssetDetails) -> Option<Cover> {
+    let metadata = BookMetadata::from_value(asset_details.onchain_metadata.as_ref()?)?;
+    let file = metadata.files.into_iter().next()?;
+    let cid = file.cid().map(str::to_owned);
+    let src = file.src?;
+    Some(Cover {
+        name: metadata.title.unwrap_or_else(|| "<Unknown>".to_owned()),
+        cid: cid.unwrap_or_else(|| src.clone()),
+        media_type: file.media_type,
+        path: src,
+    })
+}
+
+/// Decodes the hex encoded asset name, `None` if it isn't valid utf-8 text
+pub fn decode_asset_name(hex: &str) -> Option<String> {
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
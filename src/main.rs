@@ -1,22 +1,23 @@
 use blockfrost::{load, AssetPolicy, BlockFrostApi};
-use bytes::Bytes;
-use serde::Deserialize;
-use sha2::{Digest, Sha256};
+use book_cli::{
+    cid, config, decode_asset_name, get_high_res_cover, jobs::JobsFile, manifest::ManifestEntry,
+    metrics, policy::QUARANTINE_DIR, receipt, receipt::sha256_hex, storage, timestamp,
+    BookMetadata, Chaos, Claims, CollectionSource, CoverFetcher, CoverPolicy, ExpectedCids,
+    GatewayAuth, ImageFormat, Lang, Manifest, ManifestLayout, Message, MinThroughput,
+    ProgressEvent, ProgressSink, RunSummary, Trust, VerifyEvent,
+};
+use futures::StreamExt;
+use serde_json::json;
 use std::env;
 use std::error::Error;
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashMap, HashSet},
     fs::{self},
-    path::Path,
+    path::{Path, PathBuf},
 };
-use tokio_retry::strategy::{jitter, ExponentialBackoff};
-use tokio_retry::Retry;
-
-const BOOK_IO_COLLECTIONS_URL: &str = "https://api.book.io/api/v0/collections";
 
 /// build Blockfrost api from configuration
-fn build_bf_api() -> blockfrost::Result<BlockFrostApi> {
-    let configurations = load::configurations_from_env()?;
+fn build_bf_api(configurations: &toml::Value) -> blockfrost::Result<BlockFrostApi> {
     let project_id = configurations["project_id"].as_str().unwrap();
     let api = BlockFrostApi::new(project_id, Default::default());
     Ok(api)
@@ -24,141 +25,948 @@ fn build_bf_api() -> blockfrost::Result<BlockFrostApi> {
 
 // Simplifies passing around the configuration parameters
 struct Config<'a> {
-    api: &'a BlockFrostApi,
-    ipfs_gateway: &'a str,
+    fetcher: &'a CoverFetcher,
     work_dir: &'a str,
+    receipts: bool,
+    refresh: bool,
+    manifest_layout: Option<ManifestLayout>,
+    progress: &'a ProgressSink,
+    expected_cids: &'a ExpectedCids,
+    cover_policy: &'a CoverPolicy,
+    lang: Lang,
+    summary: &'a RunSummary,
+    min_quantity: u64,
+    claims: Option<&'a Claims>,
+}
+
+impl Config<'_> {
+    /// sends the event to the progress listener and tallies it in the run summary
+    fn emit(&self, event: ProgressEvent) {
+        self.progress.emit(&event);
+        self.summary.count(&event);
+    }
+}
+
+/// prints a user facing message in the output language
+fn say(lang: Lang, message: Message) {
+    println!("{}", message.text(lang));
+}
+
+/// policy ids allowed to be downloaded in locked mode (`allowed_policies` in the configuration)
+fn allowed_policies(configurations: &toml::Value) -> HashSet<String> {
+    configurations
+        .get("allowed_policies")
+        .and_then(|policies| policies.as_array())
+        .map(|policies| {
+            policies
+                .iter()
+                .filter_map(|policy| policy.as_str().map(|str| str.to_owned()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// locked mode can be forced from the configuration (`locked = true`) so it can't be bypassed from the command line
+fn is_locked(configurations: &toml::Value, flags: &HashMap<String, String>) -> bool {
+    flags.contains_key("--locked")
+        || configurations
+            .get("locked")
+            .and_then(|locked| locked.as_bool())
+            .unwrap_or(false)
+}
+
+/// collection catalog from `--collections=<source>` or the `collections` configuration entry
+fn collection_source(
+    configurations: &toml::Value,
+    flags: &HashMap<String, String>,
+) -> CollectionSource {
+    flags
+        .get("--collections")
+        .map(|source| source.as_str())
+        .or_else(|| configurations.get("collections").and_then(|c| c.as_str()))
+        .map(CollectionSource::parse)
+        .unwrap_or_default()
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    //number of files to process on each
-    let chunk_size = 10;
+    //parse command line arguments, flags (`--name` or `--name=value`) can go anywhere
+    let (flags, mut args): (Vec<String>, Vec<String>) =
+        env::args().partition(|arg| arg.starts_with("--"));
+    let flags: HashMap<String, String> = flags
+        .into_iter()
+        .map(|flag| match flag.split_once('=') {
+            Some((name, value)) => (name.to_owned(), value.to_owned()),
+            None => (flag, String::new()),
+        })
+        .collect();
+
+    //subcommands go before the policy id:
+    //`verify` checks the covers already downloaded instead of downloading them
+    //`sample` downloads a few covers to a temp directory, for quick demos and checking the configuration
+    //`metadata` prints an asset's metadata (takes an asset id instead of a policy id)
+    //`run-jobs` downloads the collections described in a jobs file (takes its path instead of a policy id)
+    //`fix-extensions` renames the files of a work dir after their format (takes the work dir instead of a policy id)
+    //`state` queries the work dir records (takes a state command instead of a policy id)
+    //`adopt` registers covers downloaded by other tools (takes their dir before the work dir)
+    //`config show` prints the effective configuration (takes `show` instead of a policy id)
+    //`refetch` downloads the listed covers again (takes the work dir instead of a policy id)
+    let command = match args.get(1).map(|arg| arg.as_str()) {
+        Some("verify")
+        | Some("sample")
+        | Some("metadata")
+        | Some("run-jobs")
+        | Some("fix-extensions")
+        | Some("state")
+        | Some("adopt")
+        | Some("config")
+        | Some("refetch") => args.remove(1),
+        _ => String::from("download"),
+    };
+    let verify = command == "verify";
+    let sample = command == "sample";
+    let metadata = command == "metadata";
+    let run_jobs = command == "run-jobs";
+    let fix_extensions = command == "fix-extensions";
+    let state = command == "state";
+    let adopt = command == "adopt";
+    let show_config = command == "config";
+    let refetch = command == "refetch";
+
+    //human readable output in `--lang=en|es|pt` or the locale's language
+    let lang = Lang::detect(flags.get("--lang").map(|lang| lang.as_str()))?;
 
-    //parse command line arguments
-    let args: Vec<String> = env::args().collect();
     if args.len() == 1 {
-        println!("Missing policy id");
-        println!("Usage: \tbook_cli <policy_id> <work_dir>? <total_files>?");
-        println!("\tpolicy_id (mandatory): policy id of the asset");
-        println!("\twork_dir (optional): directory where to store the files (default: current directory)");
-        println!("\ttotal_files (optional): maximum number of files to download (default: 10)");
-        println!("\tipfs http gateway (optional): url of the ipfs gateway (default: https://ipfs.io/ipfs/)");
+        say(lang, Message::MissingPolicyId);
+        say(lang, Message::Usage);
 
         return Ok(());
     }
 
     // load command line parameters, there's probably a rust crate that does it better
-    let policy_id = &args[1];
-    let work_dir: String = args.get(2).unwrap_or(&String::from(".")).to_owned();
-    let max_files = args
+    //asset ids start with their policy id
+    let policy_id = if metadata {
+        args[1].get(..56).unwrap_or(&args[1])
+    } else {
+        &args[1]
+    };
+    let mut work_dir: String = args.get(2).unwrap_or(&String::from(".")).to_owned();
+    let mut max_files = args
         .get(3)
         .and_then(|max| max.parse::<u32>().ok())
         .unwrap_or(10);
-    let gateway: String = args
-        .get(4)
+    let mut gateway: String = args
+        .get(if verify { 3 } else { 4 })
         .unwrap_or(&"https://ipfs.io/ipfs/".to_owned())
         .to_owned();
 
-    let api = build_bf_api()?;
+    if sample {
+        //everything but the policy id is fixed, so the sample needs no other configuration than Blockfrost's
+        let sample_dir = env::temp_dir().join(format!("book_cli-sample-{}", policy_id));
+        fs::create_dir_all(&sample_dir)?;
+        work_dir = sample_dir.to_string_lossy().into_owned();
+        max_files = 3;
+        gateway = "https://ipfs.io/ipfs/".to_owned();
+    }
+
+    //fixing extensions is local, it doesn't need any configuration
+    if fix_extensions {
+        return fix_file_extensions(lang, Path::new(&args[1])).await;
+    }
+    //and so is querying the work dir state
+    if state {
+        return state_command(lang, &args[1..], &flags).await;
+    }
+
+    let configurations = load::configurations_from_env()?;
+
+    if show_config {
+        if args[1] != "show" {
+            return Err(format!("unknown config command {:#?}", args[1]).into());
+        }
+        let config = effective_config(&configurations, &flags, lang)?;
+        println!("{}", serde_json::to_string_pretty(&config)?);
+        return Ok(());
+    }
 
+    if run_jobs {
+        return run_jobs_file(lang, &configurations, &flags, Path::new(&args[1])).await;
+    }
+
+    //in locked mode (e.g. kiosk deployments) only the configured policies can be downloaded
+    let locked = is_locked(&configurations, &flags);
+
+    //the covers to download again are listed by asset or cid, their policies are checked one by one
+    if refetch {
+        let gateway = args.get(2).map(|gateway| gateway.as_str());
+        let fetcher = build_fetcher(
+            &configurations,
+            &flags,
+            gateway.unwrap_or("https://ipfs.io/ipfs/"),
+        )?;
+        let allowed = locked.then(|| allowed_policies(&configurations));
+        return refetch_covers(
+            lang,
+            &fetcher,
+            &flags,
+            allowed.as_ref(),
+            Path::new(&args[1]),
+        )
+        .await;
+    }
+
+    if locked && !allowed_policies(&configurations).contains(policy_id) {
+        say(lang, Message::NotAllowedInLockedMode { policy_id });
+        return Ok(());
+    }
+
+    let fetcher = build_fetcher(&configurations, &flags, &gateway)?;
+
+    if verify {
+        return verify_covers(lang, &fetcher, policy_id, &work_dir).await;
+    }
+    if adopt {
+        let dir = args.get(2).ok_or("missing directory to adopt")?;
+        let work_dir = args.get(3).map(|dir| dir.as_str()).unwrap_or(".");
+        return adopt_files(
+            lang,
+            &fetcher,
+            policy_id,
+            Path::new(dir),
+            Path::new(work_dir),
+        )
+        .await;
+    }
+    if metadata {
+        return print_metadata(&fetcher, &args[1], flags.contains_key("--raw")).await;
+    }
+
+    let progress = progress_sink(&flags, lang)?;
+    let expected_cids = expected_cids(&flags)?;
+    let cover_policy = cover_policy(&configurations)?;
+    let summary = RunSummary::default();
+    let claims = claims(&flags)?;
     let config = Config {
-        api: &api,
-        ipfs_gateway: &gateway,
+        fetcher: &fetcher,
         work_dir: &work_dir,
+        receipts: flags.contains_key("--receipts"),
+        refresh: flags.contains_key("--refresh"),
+        manifest_layout: manifest_layout(&flags)?,
+        progress: &progress,
+        expected_cids: &expected_cids,
+        cover_policy: &cover_policy,
+        lang,
+        summary: &summary,
+        min_quantity: min_quantity(&flags)?,
+        claims: claims.as_ref(),
     };
 
-    //read collections from book.io
-    let collection_ids = collections().await?;
+    //read collections from the catalog (book.io unless configured otherwise)
+    let collection_ids = collection_source(&configurations, &flags)
+        .collections()
+        .await?;
 
     //keep track of already processed files
     let mut file_hashes: HashSet<String> = HashSet::new();
 
-    if collection_ids.contains(policy_id) {
-        let mut file_count: u32 = 0;
+    if let Some(assets) =
+        known_policy(lang, &fetcher, &collection_ids, policy_id, trust(&flags)?).await?
+    {
+        let manifest =
+            download_collection(&config, &mut file_hashes, policy_id, assets, max_files).await?;
+        summary.add_transfers(fetcher.transfers());
+        report(lang, &summary, &flags).await?;
 
-        //read the asset's policies and process them by chunks (so we can stop when we have enough files)
-        let assets = api.assets_policy_by_id(policy_id).await?;
+        if sample {
+            for asset in manifest.covers().keys() {
+                if let Some(file) = manifest.cover_file(Path::new(&work_dir), asset) {
+                    println!("{}", file.display());
+                }
+            }
+        }
+    }
 
-        let chunks = assets.chunks(chunk_size);
-        for chunk in chunks {
-            //fetch the files for each chunk of policies
-            file_count += fetch_files(
-                &config,
-                &mut file_hashes,
-                &chunk.to_vec(),
-                max_files - file_count,
+    Ok(())
+}
+
+/// manifest layout to convert to from `--manifest-layout=single|sharded`, if set
+fn manifest_layout(
+    flags: &HashMap<String, String>,
+) -> Result<Option<ManifestLayout>, Box<dyn Error>> {
+    match flags.get("--manifest-layout") {
+        Some(layout) => match ManifestLayout::parse(layout) {
+            Some(layout) => Ok(Some(layout)),
+            None => Err(format!("unknown manifest layout {:#?}", layout).into()),
+        },
+        None => Ok(None),
+    }
+}
+
+/// progress events go to the `--progress-socket=<path>` socket or named pipe, if set
+fn progress_sink(flags: &HashMap<String, String>, lang: Lang) -> std::io::Result<ProgressSink> {
+    match flags.get("--progress-socket") {
+        Some(path) => Ok(ProgressSink::connect(Path::new(path))?.with_lang(lang)),
+        None => Ok(ProgressSink::default()),
+    }
+}
+
+/// cover fetcher for a gateway, authenticated if it's one of the configured `gateway_auth` and
+/// watched if it has a configured `min_throughput`
+fn build_fetcher(
+    configurations: &toml::Value,
+    flags: &HashMap<String, String>,
+    gateway: &str,
+) -> Result<CoverFetcher, Box<dyn Error>> {
+    let mut fetcher = CoverFetcher::new(build_bf_api(configurations)?, gateway);
+    if let Some(gateway_auths) = configurations.get("gateway_auth") {
+        let gateway_auths: Vec<GatewayAuth> = gateway_auths.clone().try_into()?;
+        if let Some(auth) = gateway_auths
+            .into_iter()
+            .find(|auth| auth.gateway == gateway)
+        {
+            fetcher = fetcher.with_gateway_auth(auth);
+        }
+    }
+    if let Some(min_throughputs) = configurations.get("min_throughput") {
+        let min_throughputs: Vec<MinThroughput> = min_throughputs.clone().try_into()?;
+        if let Some(min_throughput) = min_throughputs
+            .into_iter()
+            .find(|min_throughput| min_throughput.gateway == gateway)
+        {
+            fetcher = fetcher.with_min_throughput(min_throughput);
+        }
+    }
+    with_chaos(fetcher, flags)
+}
+
+/// fault injection for testing, from the hidden `--chaos=<seed>` and `--chaos-rate=<0..1>` flags:
+/// runs interrupted by the injected faults are expected to converge to a consistent work dir when run again
+fn with_chaos(
+    fetcher: CoverFetcher,
+    flags: &HashMap<String, String>,
+) -> Result<CoverFetcher, Box<dyn Error>> {
+    let seed = match flags.get("--chaos") {
+        Some(seed) => seed.parse::<u64>()?,
+        None => return Ok(fetcher),
+    };
+    let mut chaos = Chaos::new(seed);
+    if let Some(rate) = flags.get("--chaos-rate") {
+        chaos = chaos.with_rate(rate.parse()?);
+    }
+    Ok(fetcher.with_chaos(chaos))
+}
+
+/// asset claims shared by the workers splitting a collection, from `--claims-dir=<shared dir>`, if set;
+/// workers are named by `--worker=<name>`
+fn claims(flags: &HashMap<String, String>) -> Result<Option<Claims>, Box<dyn Error>> {
+    match (flags.get("--claims-dir"), worker(flags)?) {
+        (Some(dir), Some(worker)) => Ok(Some(Claims::new(Path::new(dir), worker)?)),
+        _ => Ok(None),
+    }
+}
+
+/// name of this worker in the claims, `--worker=<name>`, required with `--claims-dir`: a worker run
+/// again resumes the claims made under the same name, so it can't be made up for each run
+fn worker(flags: &HashMap<String, String>) -> Result<Option<&str>, Box<dyn Error>> {
+    match (flags.get("--claims-dir"), flags.get("--worker")) {
+        (Some(_), None) => Err("--claims-dir requires --worker=<name>".into()),
+        (Some(_), Some(worker)) => Ok(Some(worker)),
+        (None, _) => Ok(None),
+    }
+}
+
+/// the settings a run with these flags uses, resolved as the run does, with the secrets redacted
+fn effective_config(
+    configurations: &toml::Value,
+    flags: &HashMap<String, String>,
+    lang: Lang,
+) -> Result<serde_json::Value, Box<dyn Error>> {
+    let collections = match collection_source(configurations, flags) {
+        CollectionSource::Url(url) => url,
+        CollectionSource::File(path) => path.display().to_string(),
+        CollectionSource::None => "none".to_owned(),
+    };
+    let mut allowed_policies: Vec<String> = allowed_policies(configurations).into_iter().collect();
+    allowed_policies.sort();
+    //validated as the run would
+    manifest_layout(flags)?;
+    cover_policy(configurations)?;
+    trust(flags)?;
+    Ok(json!({
+        "configuration": config::redacted(configurations),
+        "collections": collections,
+        "trust": flags.get("--trust"),
+        "locked": is_locked(configurations, flags),
+        "allowed_policies": allowed_policies,
+        "lang": format!("{:?}", lang).to_lowercase(),
+        "receipts": flags.contains_key("--receipts"),
+        "refresh": flags.contains_key("--refresh"),
+        "manifest_layout": flags.get("--manifest-layout"),
+        "progress_socket": flags.get("--progress-socket"),
+        "expected_cids": flags.get("--expected-cids"),
+        "min_quantity": min_quantity(flags)?,
+        "claims_dir": flags.get("--claims-dir"),
+        "worker": worker(flags)?,
+        "metrics_file": flags.get("--metrics-file"),
+    }))
+}
+
+/// assets with less copies in circulation than `--min-quantity=<n>` are skipped (default: 1, any asset not burnt)
+fn min_quantity(flags: &HashMap<String, String>) -> Result<u64, Box<dyn Error>> {
+    match flags.get("--min-quantity") {
+        Some(min_quantity) => Ok(min_quantity.parse()?),
+        None => Ok(1),
+    }
+}
+
+/// cover acceptance policy from the `[cover_policy]` configuration section, accepts anything if not set
+fn cover_policy(configurations: &toml::Value) -> Result<CoverPolicy, Box<dyn Error>> {
+    match configurations.get("cover_policy") {
+        Some(cover_policy) => Ok(cover_policy.clone().try_into()?),
+        None => Ok(CoverPolicy::default()),
+    }
+}
+
+/// publisher's expected cids from the `--expected-cids=<csv file>`, if set
+fn expected_cids(flags: &HashMap<String, String>) -> Result<ExpectedCids, Box<dyn Error>> {
+    match flags.get("--expected-cids") {
+        Some(path) => ExpectedCids::load(Path::new(path)),
+        None => Ok(ExpectedCids::default()),
+    }
+}
+
+/// cross-checks the publisher's expected cid of an asset with the onchain one and, if they differ and
+/// the cover was just downloaded, its content with the content of the expected cid
+/// (the same image may have been added to ipfs with different settings, giving a different cid).
+/// Reported as unverifiable when the gateway can't provide the expected cid, the run goes on
+async fn check_expected_cid(
+    cfg: &Config<'_>,
+    asset: &str,
+    onchain: Option<&str>,
+    data: Option<&[u8]>,
+) {
+    let expected = match cfg.expected_cids.get(asset) {
+        Some(expected) if Some(expected) != onchain => expected,
+        _ => return,
+    };
+
+    //the content is the same if it has the expected cid when added with other settings, otherwise
+    //it's compared with the content of the expected cid, if the gateway has it
+    let mut unreachable = None;
+    let same_content = match data {
+        Some(data)
+            if cid::decode(expected).is_some_and(|cid| cid::local_cids(data).contains(&cid)) =>
+        {
+            Some(true)
+        }
+        Some(data) => match cfg.fetcher.download(expected).await {
+            Ok((_, expected_data)) => Some(sha256_hex(&expected_data) == sha256_hex(data)),
+            Err(err) => {
+                unreachable = Some(err.to_string());
+                None
+            }
+        },
+        None => None,
+    };
+    let message = match (onchain, same_content, &unreachable) {
+        (None, _, _) => Message::NoCoverExpected { asset, expected },
+        (Some(onchain), None, Some(error)) => Message::ExpectedCidUnverifiable {
+            asset,
+            onchain,
+            expected,
+            error,
+        },
+        (Some(onchain), Some(true), _) => Message::ExpectedCidSameContent {
+            asset,
+            onchain,
+            expected,
+        },
+        (Some(onchain), Some(false), _) => Message::ExpectedCidDifferentContent {
+            asset,
+            onchain,
+            expected,
+        },
+        (Some(recorded), None, None) => Message::RecordedCidMismatch {
+            asset,
+            recorded,
+            expected,
+        },
+    };
+    say(cfg.lang, message);
+    cfg.emit(ProgressEvent::ExpectedCidMismatch {
+        asset,
+        expected,
+        onchain,
+        same_content,
+    });
+}
+
+/// which source to follow when the catalog and the chain disagree, `--trust=chain|catalog`
+fn trust(flags: &HashMap<String, String>) -> Result<Option<Trust>, Box<dyn Error>> {
+    match flags.get("--trust") {
+        Some(trust) => match Trust::parse(trust) {
+            Some(trust) => Ok(Some(trust)),
+            None => Err(format!(
+                "unknown source to trust {:#?}, expected chain or catalog",
+                trust
             )
-            .await?;
+            .into()),
+        },
+        None => Ok(None),
+    }
+}
+
+/// whether a collection is to be downloaded, with its assets onchain if it is, see [`follow_sources`]
+async fn known_policy(
+    lang: Lang,
+    fetcher: &CoverFetcher,
+    collection_ids: &Option<HashSet<String>>,
+    policy_id: &str,
+    trust: Option<Trust>,
+) -> Result<Option<Vec<AssetPolicy>>, Box<dyn Error>> {
+    let assets = onchain_assets(fetcher, policy_id).await?;
+    follow_sources(lang, assets, collection_ids, policy_id, trust)
+}
+
+/// assets of a collection onchain, none if Blockfrost rejects the policy id (malformed, or unknown)
+async fn onchain_assets(
+    fetcher: &CoverFetcher,
+    policy_id: &str,
+) -> blockfrost::Result<Vec<AssetPolicy>> {
+    match fetcher.assets(policy_id).await {
+        Err(blockfrost::Error::Response { reason, .. })
+            if reason.status_code == 400 || reason.status_code == 404 =>
+        {
+            Ok(Vec::new())
+        }
+        result => result,
+    }
+}
 
-            if file_count >= max_files {
-                break;
+/// whether a collection is to be downloaded: it must be in the catalog (without a catalog any policy id
+/// is accepted) and have assets onchain. When only one of them has it, says which source is inconsistent
+/// and follows the trusted one, if any: the chain to download what the catalog misses, the catalog
+/// to fail when the chain doesn't have one of its collections.
+/// Returns the assets of the collection if it's to be downloaded
+fn follow_sources(
+    lang: Lang,
+    assets: Vec<AssetPolicy>,
+    collection_ids: &Option<HashSet<String>>,
+    policy_id: &str,
+    trust: Option<Trust>,
+) -> Result<Option<Vec<AssetPolicy>>, Box<dyn Error>> {
+    let in_catalog = match collection_ids {
+        Some(ids) => ids.contains(policy_id),
+        None => return Ok(Some(assets)),
+    };
+    match (in_catalog, !assets.is_empty()) {
+        (true, true) => Ok(Some(assets)),
+        (false, false) => {
+            say(lang, Message::InvalidPolicyId { policy_id });
+            Ok(None)
+        }
+        (false, true) => {
+            let downloading = trust == Some(Trust::Chain);
+            say(
+                lang,
+                Message::ChainOnly {
+                    policy_id,
+                    downloading,
+                },
+            );
+            Ok(Some(assets).filter(|_| downloading))
+        }
+        (true, false) => {
+            say(lang, Message::CatalogOnly { policy_id });
+            match trust {
+                Some(Trust::Catalog) => Err(format!(
+                    "collection {:#?} of the trusted catalog not found onchain",
+                    policy_id
+                )
+                .into()),
+                _ => Ok(None),
             }
         }
-    } else {
-        print!("invalid policy id {:#?}", policy_id);
     }
+}
+
+/// downloads up to `max_files` covers of a collection (its `assets`, as read from the chain) to the
+/// work dir, returning the updated manifest
+async fn download_collection(
+    cfg: &Config<'_>,
+    file_hashes: &mut HashSet<String>,
+    policy_id: &str,
+    assets: Vec<AssetPolicy>,
+    max_files: u32,
+) -> Result<Manifest, Box<dyn Error>> {
+    //number of files to process on each
+    let chunk_size = 10;
+
+    let mut manifest = Manifest::load(Path::new(cfg.work_dir))?;
+    if let Some(layout) = cfg.manifest_layout {
+        manifest.set_layout(layout);
+        manifest.save(Path::new(cfg.work_dir)).await?;
+    }
+    let mut file_count: u32 = 0;
+
+    //process the asset's policies by chunks (so we can stop when we have enough files)
+    let chunks = assets.chunks(chunk_size);
+    for chunk in chunks {
+        //fetch the files for each chunk of policies
+        file_count += fetch_files(
+            cfg,
+            file_hashes,
+            &mut manifest,
+            &chunk.to_vec(),
+            max_files - file_count,
+        )
+        .await?;
+
+        if file_count >= max_files {
+            break;
+        }
+    }
+    //quantities of the assets already downloaded may have changed
+    if manifest.has_changes() {
+        manifest.save(Path::new(cfg.work_dir)).await?;
+    }
+    cfg.emit(ProgressEvent::CollectionFinished {
+        policy_id,
+        files: file_count,
+    });
+    Ok(manifest)
+}
 
+/// downloads each group of collections in the jobs file to its own work dir
+async fn run_jobs_file(
+    lang: Lang,
+    configurations: &toml::Value,
+    flags: &HashMap<String, String>,
+    jobs_file: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let jobs = JobsFile::load(jobs_file)?;
+    let collection_ids = collection_source(configurations, flags)
+        .collections()
+        .await?;
+    let locked = is_locked(configurations, flags);
+    let allowed = allowed_policies(configurations);
+    let progress = progress_sink(flags, lang)?;
+    let expected_cids = expected_cids(flags)?;
+    let cover_policy = cover_policy(configurations)?;
+    let summary = RunSummary::default();
+    let claims = claims(flags)?;
+    let trust = trust(flags)?;
+
+    for group in jobs.groups {
+        say(lang, Message::RunningGroup { name: &group.name });
+        fs::create_dir_all(&group.work_dir)?;
+        let gateway = group.gateway.as_deref().unwrap_or("https://ipfs.io/ipfs/");
+        let fetcher = build_fetcher(configurations, flags, gateway)?;
+        let config = Config {
+            fetcher: &fetcher,
+            work_dir: &group.work_dir,
+            receipts: flags.contains_key("--receipts"),
+            refresh: flags.contains_key("--refresh"),
+            manifest_layout: manifest_layout(flags)?,
+            progress: &progress,
+            expected_cids: &expected_cids,
+            cover_policy: &cover_policy,
+            lang,
+            summary: &summary,
+            min_quantity: min_quantity(flags)?,
+            claims: claims.as_ref(),
+        };
+
+        let mut file_hashes: HashSet<String> = HashSet::new();
+        for policy_id in &group.policies {
+            if locked && !allowed.contains(policy_id) {
+                say(lang, Message::NotAllowedInLockedMode { policy_id });
+            } else if let Some(assets) =
+                known_policy(lang, &fetcher, &collection_ids, policy_id, trust).await?
+            {
+                download_collection(
+                    &config,
+                    &mut file_hashes,
+                    policy_id,
+                    assets,
+                    group.total_files,
+                )
+                .await?;
+            }
+        }
+        summary.add_transfers(fetcher.transfers());
+    }
+    report(lang, &summary, flags).await?;
     Ok(())
 }
 
+/// prints the run summary, and writes its metrics snapshot to `--metrics-file=<path>` if set
+async fn report(
+    lang: Lang,
+    summary: &RunSummary,
+    flags: &HashMap<String, String>,
+) -> std::io::Result<()> {
+    let counts = summary.counts();
+    say(lang, Message::Summary(counts));
+    match flags.get("--metrics-file") {
+        Some(path) => metrics::write_metrics(Path::new(path), &counts).await,
+        None => Ok(()),
+    }
+}
+
 /// fetch the files for a list of asset policies up to `files_needed` and
 /// checking if the file is already present by name (uses the policy id) or by content (uses the hash and checks `file_hashes` )
+/// when refreshing, files recorded in the `manifest` are downloaded again if their cover cid changed
 async fn fetch_files<'a>(
     cfg: &Config<'a>,
     file_hashes: &mut HashSet<String>,
+    manifest: &mut Manifest,
     assets: &Vec<AssetPolicy>,
     files_needed: u32,
 ) -> Result<u32, Box<dyn Error>> {
     let mut found_files = 0;
     for asset in assets {
-        let temp_filename = cfg.work_dir.to_owned() + "/" + &asset.asset + ".tmp";
-        let filename = cfg.work_dir.to_owned() + "/" + &asset.asset;
-
-        let qty: i32 = asset.quantity.parse()?;
+        let quantity: u64 = asset.quantity.parse()?;
 
         if found_files >= files_needed {
             //stop the iteration if we have enough files
             break;
         };
 
-        if qty > 0 {
-            if !Path::new(&filename).exists() {
-                let asset_details = cfg.api.assets_by_id(&asset.asset).await?;
-                match get_high_res_cover_path(asset_details) {
-                    Some(path) => {
-                        //drop the "ipfs://" from the path
-                        let mut cid: String = path.clone();
-                        cid.drain(0..7);
+        if quantity > 0 && quantity >= cfg.min_quantity {
+            let existing = manifest.cover_file(Path::new(cfg.work_dir), &asset.asset);
+            let exists = existing.is_some();
+            let recorded_cid = manifest.get(&asset.asset).map(|entry| entry.cid.clone());
+            //aliases share the file of another asset, they aren't counted or archived
+            let alias_of = manifest
+                .get(&asset.asset)
+                .and_then(|entry| entry.alias_of.clone());
+            if !exists || (cfg.refresh && recorded_cid.is_some()) {
+                //when the collection is split across workers, each asset is handled by the one claiming it
+                let claim = match cfg.claims {
+                    Some(claims) => match claims.claim(&asset.asset)? {
+                        Ok(claim) => Some(claim),
+                        Err(worker) => {
+                            say(
+                                cfg.lang,
+                                Message::ClaimedByOtherWorker {
+                                    asset: &asset.asset,
+                                    worker: &worker,
+                                },
+                            );
+                            cfg.emit(ProgressEvent::ClaimedByOtherWorker {
+                                asset: &asset.asset,
+                                worker: &worker,
+                            });
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                match cfg.fetcher.cover(&asset.asset).await? {
+                    Some(cover) if exists && recorded_cid.as_ref() == Some(&cover.cid) => {
+                        say(
+                            cfg.lang,
+                            Message::UpToDate {
+                                asset: &asset.asset,
+                            },
+                        );
+                        check_expected_cid(cfg, &asset.asset, Some(&cover.cid), None).await;
+                        manifest.set_quantity(&asset.asset, quantity);
+                        cfg.emit(ProgressEvent::AlreadyDownloaded {
+                            asset: &asset.asset,
+                        });
+                        file_hashes.insert(cover.cid);
+                        if alias_of.is_none() {
+                            found_files += 1;
+                        }
+                    }
+                    Some(cover) => {
+                        say(cfg.lang, Message::FoundCover { name: &cover.name });
+                        let cid = cover.cid;
 
                         // download the high-res cover from ipfs network
-                        let url = cfg.ipfs_gateway.to_owned() + &cid;
-                        let asset_data = download_binary(url).await?;
+                        let url = cfg.fetcher.cover_url(&cid);
+                        let (headers, asset_data) = match cfg.fetcher.download(&cid).await {
+                            Ok(download) => download,
+                            //e.g. a cid no longer pinned, the rest of the collection can still be downloaded
+                            Err(err) if err.is_permanent() => {
+                                let error = err.to_string();
+                                say(
+                                    cfg.lang,
+                                    Message::DownloadFailed {
+                                        asset: &asset.asset,
+                                        cid: &cid,
+                                        error: &error,
+                                    },
+                                );
+                                cfg.emit(ProgressEvent::DownloadFailed {
+                                    asset: &asset.asset,
+                                    cid: &cid,
+                                    error: &error,
+                                });
+                                continue;
+                            }
+                            Err(err) => return Err(err.into()),
+                        };
+                        check_expected_cid(cfg, &asset.asset, Some(&cid), Some(&asset_data)).await;
 
                         //skip writting if we already have the image
                         if !(file_hashes.contains(cid.as_str())) {
-                            //write the data to a temp file and rename to final name
-                            fs::write(&temp_filename, asset_data)
-                                .and_then(|_| fs::rename(&temp_filename, &filename))?;
+                            if let (Some(existing), Some(old_cid), None) =
+                                (&existing, &recorded_cid, &alias_of)
+                            {
+                                //the publisher changed the cover, keep the previous one
+                                let versioned = storage::archive_version(
+                                    Path::new(cfg.work_dir),
+                                    existing,
+                                    &asset.asset,
+                                    old_cid,
+                                )?;
+                                manifest.supersede(&asset.asset, &versioned);
+                                say(
+                                    cfg.lang,
+                                    Message::CoverChanged {
+                                        asset: &asset.asset,
+                                        previous_cid: old_cid,
+                                        cid: &cid,
+                                        archived: &versioned,
+                                    },
+                                );
+                                cfg.emit(ProgressEvent::Superseded {
+                                    asset: &asset.asset,
+                                    previous_cid: old_cid,
+                                    cid: &cid,
+                                    archived: &versioned.to_string_lossy(),
+                                });
+                            }
+
+                            //write the data to a temp file and rename to final name (with the extension of its format)
+                            let mut file_name = storage::cover_file_name(&asset.asset, &asset_data);
+
+                            //covers not conforming to the policy are flagged, and kept apart when quarantining
+                            let violations = cfg.cover_policy.check(&asset_data);
+                            if !violations.is_empty() {
+                                say(
+                                    cfg.lang,
+                                    Message::PolicyViolations {
+                                        asset: &asset.asset,
+                                        violations: &violations,
+                                    },
+                                );
+                                if cfg.cover_policy.quarantine {
+                                    fs::create_dir_all(
+                                        Path::new(cfg.work_dir).join(QUARANTINE_DIR),
+                                    )?;
+                                    file_name = format!("{}/{}", QUARANTINE_DIR, file_name);
+                                }
+                                cfg.emit(ProgressEvent::CoverPolicyViolation {
+                                    asset: &asset.asset,
+                                    violations: &violations,
+                                    quarantined: cfg.cover_policy.quarantine,
+                                });
+                            }
+                            let filename = cfg.work_dir.to_owned() + "/" + &file_name;
+                            storage::write_file(&filename, &asset_data).await?;
+                            if cfg.receipts {
+                                receipt::write_receipt(
+                                    &filename,
+                                    &asset.asset,
+                                    &cid,
+                                    &url,
+                                    &headers,
+                                    &asset_data,
+                                )
+                                .await?;
+                            }
+                            manifest.record(&asset.asset, &cid, &file_name, &asset_data);
+                            manifest.set_quantity(&asset.asset, quantity);
+                            manifest.set_violations(&asset.asset, violations);
+                            manifest.save(Path::new(cfg.work_dir)).await?;
+                            cfg.emit(ProgressEvent::Downloaded {
+                                asset: &asset.asset,
+                                cid: &cid,
+                                file: &filename,
+                                bytes: asset_data.len(),
+                            });
                             file_hashes.insert(cid.to_owned());
                             found_files += 1;
                         } else {
-                            println!(
-                                "High-res cover {:#?} for asset {:#?} is the same as existing one",
-                                path, asset.asset
-                            );
+                            //recorded as an alias of the asset with the same cover, if it's in the manifest
+                            let alias_of = manifest
+                                .find_by_cid(&cid)
+                                .filter(|of| *of != asset.asset)
+                                .map(|of| of.to_owned());
+                            match &alias_of {
+                                Some(of) => {
+                                    say(
+                                        cfg.lang,
+                                        Message::AliasOf {
+                                            asset: &asset.asset,
+                                            of,
+                                        },
+                                    );
+                                    manifest.record_alias(&asset.asset, &cid, of);
+                                    manifest.set_quantity(&asset.asset, quantity);
+                                    manifest.save(Path::new(cfg.work_dir)).await?;
+                                }
+                                None => say(
+                                    cfg.lang,
+                                    Message::SameAsExisting {
+                                        path: &cover.path,
+                                        asset: &asset.asset,
+                                    },
+                                ),
+                            }
+                            cfg.emit(ProgressEvent::Duplicate {
+                                asset: &asset.asset,
+                                cid: &cid,
+                                alias_of: alias_of.as_deref(),
+                                bytes: asset_data.len(),
+                            });
                         }
                     }
                     None => {
-                        println!("Asset without high-res cover image: {:#?}", asset);
+                        say(
+                            cfg.lang,
+                            Message::NoCover {
+                                asset: &asset.asset,
+                            },
+                        );
+                        check_expected_cid(cfg, &asset.asset, None, None).await;
+                        cfg.emit(ProgressEvent::NoCover {
+                            asset: &asset.asset,
+                        });
                     }
                 }
-            } else {
-                println!("Asset {:#?} already downloaded", asset.asset);
+                //failed downloads release the claim (on drop), so another worker can take the asset
+                if let Some(claim) = claim {
+                    claim.keep();
+                }
+            } else if exists {
+                say(
+                    cfg.lang,
+                    Message::AlreadyDownloaded {
+                        asset: &asset.asset,
+                    },
+                );
+                //without fetching the metadata again, the recorded cid is what can be checked
+                let recorded = manifest.get(&asset.asset).map(|entry| entry.cid.as_str());
+                if recorded.is_some() {
+                    check_expected_cid(cfg, &asset.asset, recorded, None).await;
+                }
+                manifest.set_quantity(&asset.asset, quantity);
+                if alias_of.is_some() {
+                    continue;
+                }
+                cfg.emit(ProgressEvent::AlreadyDownloaded {
+                    asset: &asset.asset,
+                });
 
-                //calculate the hash so we don't download it again under a different name
-                let file_data = fs::read(filename)?;
-                let hash = calculate_cid(&file_data);
-                file_hashes.insert(hash);
+                //so other assets with the same cid are recorded as its aliases instead of downloaded again
+                if let Some(cid) = recorded_cid {
+                    file_hashes.insert(cid);
+                }
 
                 found_files += 1;
             }
@@ -167,70 +975,642 @@ async fn fetch_files<'a>(
     return Ok(found_files);
 }
 
-/// Downloads a binary file from an url with exponential backoff retry
-async fn download_binary(url: String) -> Result<Bytes, reqwest::Error> {
-    let retry_strategy = ExponentialBackoff::from_millis(10)
-        .map(jitter) // add jitter to delays
-        .take(3); // limit to 3 retries
-    let content = Retry::spawn(retry_strategy, || reqwest::get(url.to_owned()))
-        .await?
-        .bytes()
-        .await;
-    content
+/// renames the covers saved without extension by older runs (current ones and superseded ones in
+/// `versions/`) after the format of their content, along with their receipts and manifest references
+async fn fix_file_extensions(lang: Lang, work_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut manifest = Manifest::load(work_dir)?;
+
+    //current covers are named by asset id, superseded ones `<asset>.<timestamp>.<cid>`
+    let mut files: Vec<PathBuf> = Vec::new();
+    for dir in [work_dir.to_path_buf(), work_dir.join(storage::VERSIONS_DIR)] {
+        if !dir.is_dir() {
+            continue;
+        }
+        let current_covers = dir == work_dir;
+        for entry in fs::read_dir(&dir)? {
+            let file = entry?.path();
+            let dotted = file
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().contains('.'));
+            if file.is_file()
+                && storage::image_extension(&file).is_none()
+                && !(current_covers && dotted)
+            {
+                files.push(file);
+            }
+        }
+    }
+
+    for file in files {
+        let format = match ImageFormat::sniff(&fs::read(&file)?) {
+            Some(format) => format,
+            None => {
+                say(lang, Message::UnknownFormat { file: &file });
+                continue;
+            }
+        };
+        let fixed = with_suffix(&file, &(".".to_owned() + format.extension()));
+        fs::rename(&file, &fixed)?;
+
+        //receipts are named after the cover file
+        let receipt = with_suffix(&file, ".receipt.json");
+        if receipt.exists() {
+            fs::rename(&receipt, with_suffix(&fixed, ".receipt.json"))?;
+        }
+
+        let file_name = file.file_name().unwrap_or_default().to_string_lossy();
+        let asset = file_name.split('.').next().unwrap_or_default();
+        manifest.file_renamed(asset, &file, &fixed);
+        say(
+            lang,
+            Message::Renamed {
+                from: &file,
+                to: &fixed,
+            },
+        );
+    }
+
+    //covers are found with or without extension, so an interrupted run leaves a consistent work dir
+    //and the manifest references are fixed when running it again
+    manifest.save(work_dir).await?;
+    Ok(())
+}
+
+/// registers the covers of a collection found in `dir` (e.g. downloaded by an older script) in the
+/// work dir: files are matched to the onchain cids by computing theirs, then linked (moved if `dir`
+/// is the work dir) to the cover file names and recorded in the manifest, without downloading them
+async fn adopt_files(
+    lang: Lang,
+    fetcher: &CoverFetcher,
+    policy_id: &str,
+    dir: &Path,
+    work_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let mut manifest = Manifest::load(work_dir)?;
+
+    //the cids each file may have been added to ipfs with
+    let mut files_by_cid: HashMap<Vec<u8>, PathBuf> = HashMap::new();
+    let mut files: Vec<PathBuf> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let file = entry?.path();
+        if file.is_file() {
+            for cid in cid::local_cids(&fs::read(&file)?) {
+                files_by_cid.insert(cid, file.clone());
+            }
+            files.push(file);
+        }
+    }
+
+    //asset each file was adopted as
+    let mut adopted: HashMap<PathBuf, String> = HashMap::new();
+    let moving = fs::canonicalize(dir)? == fs::canonicalize(work_dir)?;
+    for asset in fetcher.assets(policy_id).await? {
+        let quantity = asset.quantity.parse::<u64>().unwrap_or(0);
+        if quantity == 0 || manifest.get(&asset.asset).is_some() {
+            continue;
+        }
+        let cover = match fetcher.cover(&asset.asset).await? {
+            Some(cover) => cover,
+            None => continue,
+        };
+        let file = match cid::decode(&cover.cid).and_then(|cid| files_by_cid.get(&cid)) {
+            Some(file) => file,
+            None => continue,
+        };
+
+        //assets sharing a cover share its file too
+        if let Some(of) = adopted.get(file) {
+            say(
+                lang,
+                Message::AliasOf {
+                    asset: &asset.asset,
+                    of,
+                },
+            );
+            manifest.record_alias(&asset.asset, &cover.cid, of);
+        } else {
+            let data = fs::read(file)?;
+            let file_name = storage::cover_file_name(&asset.asset, &data);
+            let adopted_file = work_dir.join(&file_name);
+            let in_place = moving && file.file_name() == adopted_file.file_name();
+            if !in_place && adopted_file.exists() {
+                //left by an interrupted run if it has the same content, otherwise it's never overwritten
+                if fs::read(&adopted_file)? != data {
+                    say(
+                        lang,
+                        Message::AdoptTargetExists {
+                            asset: &asset.asset,
+                            file: &adopted_file,
+                        },
+                    );
+                    continue;
+                }
+                if moving {
+                    fs::remove_file(file)?;
+                }
+            } else if moving {
+                fs::rename(file, &adopted_file)?;
+            } else {
+                link_or_copy(file, &adopted_file)?;
+            }
+            say(
+                lang,
+                Message::Adopted {
+                    asset: &asset.asset,
+                    file,
+                },
+            );
+            manifest.record(&asset.asset, &cover.cid, &file_name, &data);
+            adopted.insert(file.clone(), asset.asset.clone());
+        }
+        manifest.set_quantity(&asset.asset, quantity);
+    }
+    manifest.save(work_dir).await?;
+
+    for file in files.iter().filter(|file| !adopted.contains_key(*file)) {
+        say(lang, Message::NotAdopted { file });
+    }
+    Ok(())
 }
 
-///hash using sha2-256 (same as ipfs)
-fn calculate_cid(t: &Vec<u8>) -> String {
-    let mut s = Sha256::new();
-    s.update(t);
-    return String::from_utf8_lossy(&s.finalize()[..]).to_string();
+/// hard links `file` as `to`, copying it only if it's on another file system
+fn link_or_copy(file: &Path, to: &Path) -> std::io::Result<()> {
+    match fs::hard_link(file, to) {
+        Err(err) if err.kind() == std::io::ErrorKind::CrossesDevices => {
+            fs::copy(file, to).map(|_| ())
+        }
+        result => result,
+    }
 }
 
-///Extracts the high-res cover path from the asset's onchain metadata
-fn get_high_res_cover_path(asset_details: blockfrost::AssetDetails) -> Option<String> {
-    let o_path = asset_details.onchain_metadata.and_then(|json| {
-        let path = json["files"][0]["src"].as_str().map(|str| str.to_owned());
-        println!(
-            "Found high-res cover for {:#?}",
-            json["name"].as_str().unwrap_or("<Unknown>")
+/// downloads again the covers listed by asset id (`--assets=<file>`) and/or cid (`--cids=<file>`),
+/// whether their files exist or not, e.g. to repair covers corrupted by a gateway. The covers in the
+/// manifest are downloaded by their recorded cid, the rest by their onchain one, the listed cids
+/// not recorded are reported. Receipts are written again, or written with `--receipts`.
+/// In locked mode (`allowed` set) the covers of policies not allowed are refused
+async fn refetch_covers(
+    lang: Lang,
+    fetcher: &CoverFetcher,
+    flags: &HashMap<String, String>,
+    allowed: Option<&HashSet<String>>,
+    work_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    if !flags.contains_key("--assets") && !flags.contains_key("--cids") {
+        return Err("missing --cids=<file> or --assets=<file>".into());
+    }
+    let receipts = flags.contains_key("--receipts");
+    let mut manifest = Manifest::load(work_dir)?;
+
+    let mut assets: BTreeSet<String> = BTreeSet::new();
+    if let Some(path) = flags.get("--assets") {
+        for asset in read_list(Path::new(path))? {
+            //aliases share the file of the asset they are an alias of
+            let alias_of = manifest
+                .get(&asset)
+                .and_then(|entry| entry.alias_of.clone());
+            assets.insert(alias_of.unwrap_or(asset));
+        }
+    }
+    if let Some(path) = flags.get("--cids") {
+        let cids: HashSet<String> = read_list(Path::new(path))?
+            .into_iter()
+            .map(|cid| cid.strip_prefix("ipfs://").unwrap_or(&cid).to_owned())
+            .collect();
+        assets.extend(
+            manifest
+                .covers()
+                .iter()
+                .filter(|(_, entry)| entry.alias_of.is_none() && cids.contains(&entry.cid))
+                .map(|(asset, _)| asset.clone()),
         );
-        return path;
-    });
-    o_path
+        let recorded: HashSet<&str> = manifest
+            .covers()
+            .values()
+            .map(|entry| entry.cid.as_str())
+            .collect();
+        let mut unmatched: Vec<&String> = cids
+            .iter()
+            .filter(|cid| !recorded.contains(cid.as_str()))
+            .collect();
+        unmatched.sort();
+        for cid in unmatched {
+            say(lang, Message::UnmatchedCid { cid });
+        }
+    }
+
+    for asset in assets {
+        //asset ids start with their policy id
+        let policy_id = asset.get(..56).unwrap_or(&asset);
+        if allowed.is_some_and(|allowed| !allowed.contains(policy_id)) {
+            say(lang, Message::NotAllowedInLockedMode { policy_id });
+            continue;
+        }
+        let entry = manifest.get(&asset).cloned();
+        let cid = match &entry {
+            Some(entry) => entry.cid.clone(),
+            None => match fetcher.cover(&asset).await? {
+                Some(cover) => cover.cid,
+                None => {
+                    say(lang, Message::NoCover { asset: &asset });
+                    continue;
+                }
+            },
+        };
+        let (headers, data) = match fetcher.download(&cid).await {
+            Ok(downloaded) => downloaded,
+            Err(err) if err.is_permanent() => {
+                let error = err.to_string();
+                say(
+                    lang,
+                    Message::DownloadFailed {
+                        asset: &asset,
+                        cid: &cid,
+                        error: &error,
+                    },
+                );
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        //named after the format of the content downloaded now, quarantined covers stay apart
+        let existing = manifest.cover_file(work_dir, &asset);
+        let mut file_name = storage::cover_file_name(&asset, &data);
+        let quarantined = entry
+            .as_ref()
+            .and_then(|entry| entry.file.as_ref())
+            .is_some_and(|file| file.starts_with(&format!("{}/", QUARANTINE_DIR)));
+        if quarantined {
+            file_name = format!("{}/{}", QUARANTINE_DIR, file_name);
+        }
+        let file = work_dir.join(&file_name);
+        storage::write_file(&file.to_string_lossy(), &data).await?;
+        //the receipt describes the content downloaded now, also when the previous cover had one
+        let had_receipt = existing
+            .as_ref()
+            .is_some_and(|existing| with_suffix(existing, ".receipt.json").exists());
+        if receipts || had_receipt {
+            receipt::write_receipt(
+                &file.to_string_lossy(),
+                &asset,
+                &cid,
+                &fetcher.cover_url(&cid),
+                &headers,
+                &data,
+            )
+            .await?;
+        }
+        if let Some(existing) = existing.filter(|existing| *existing != file) {
+            fs::remove_file(&existing)?;
+            let previous_receipt = with_suffix(&existing, ".receipt.json");
+            if previous_receipt.exists() {
+                fs::remove_file(previous_receipt)?;
+            }
+        }
+
+        manifest.record(&asset, &cid, &file_name, &data);
+        if let Some(entry) = entry {
+            if let Some(quantity) = entry.quantity {
+                manifest.set_quantity(&asset, quantity);
+            }
+            manifest.set_violations(&asset, entry.violations);
+        }
+        manifest.save(work_dir).await?;
+        say(
+            lang,
+            Message::Refetched {
+                asset: &asset,
+                file: &file_name,
+            },
+        );
+    }
+    Ok(())
 }
 
-//structs representing book.io json response
-#[derive(Debug, Deserialize)]
-struct CollectionsResponse {
-    #[serde(rename = "type")]
-    data_type: String,
-    data: Vec<DataEntry>,
+/// the lines of a list file, `#` comments and blank lines skipped
+fn read_list(path: &Path) -> std::io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_owned())
+        .collect())
 }
 
-#[derive(Debug, Deserialize)]
-struct DataEntry {
-    collection_id: String,
-    description: String,
-    blockchain: String,
-    network: String,
+/// `state at <date> <work_dir>?`: the covers of the work dir at a past date, reconstructed from
+/// the manifest (current covers and superseded versions, with their times)
+/// `state fsck <work_dir>? [--repair]`: cross-checks the manifest and the files of the work dir
+async fn state_command(
+    lang: Lang,
+    args: &[String],
+    flags: &HashMap<String, String>,
+) -> Result<(), Box<dyn Error>> {
+    match args.first().map(|command| command.as_str()) {
+        Some("fsck") => {
+            let work_dir = args.get(1).map(|dir| dir.as_str()).unwrap_or(".");
+            fsck(lang, Path::new(work_dir), flags.contains_key("--repair")).await?;
+            Ok(())
+        }
+        Some("at") => {
+            let date = args.get(1).ok_or("missing date")?;
+            let at = timestamp::parse_point_in_time(date)
+                .ok_or(format!("invalid date {:#?}, expected YYYY-MM-DD", date))?;
+            let work_dir = args.get(2).map(|dir| dir.as_str()).unwrap_or(".");
+            let manifest = Manifest::load(Path::new(work_dir))?;
+
+            let covers = manifest.covers_at(&at);
+            let cids: HashSet<&str> = covers.values().copied().collect();
+            say(
+                lang,
+                Message::StateAt {
+                    at: &at,
+                    covers: covers.len(),
+                    cids: cids.len(),
+                },
+            );
+            for (asset, cid) in covers {
+                println!("{} {}", asset, cid);
+            }
+            Ok(())
+        }
+        Some(command) => Err(format!("unknown state command {:#?}", command).into()),
+        None => Err("missing state command".into()),
+    }
 }
 
-/// Fetchs the full list of policies from book.io
-async fn collections() -> Result<HashSet<String>, reqwest::Error> {
-    let client = reqwest::Client::new();
-    //to policy_id set
+/// reports the covers recorded without their file (or with different content), the aliases of assets
+/// without their cover, the missing superseded covers and the cover files not recorded.
+/// Repairing forgets the missing covers and dangling aliases (so the next run downloads them again)
+/// and compacts the manifest, the files are never touched. Returns how many problems were found
+async fn fsck(lang: Lang, work_dir: &Path, repair: bool) -> Result<usize, Box<dyn Error>> {
+    let mut manifest = Manifest::load(work_dir)?;
+    let entries: Vec<(String, ManifestEntry)> = manifest
+        .covers()
+        .iter()
+        .map(|(asset, entry)| (asset.clone(), entry.clone()))
+        .collect();
+    let mut problems = 0;
 
-    // Send the GET request
-    let response = client.get(BOOK_IO_COLLECTIONS_URL).send().await?;
+    let mut tracked: HashSet<PathBuf> = HashSet::new();
+    let mut missing: HashSet<&str> = HashSet::new();
+    for (asset, entry) in entries.iter().filter(|(_, entry)| entry.alias_of.is_none()) {
+        match manifest.cover_file(work_dir, asset) {
+            Some(file) => {
+                if sha256_hex(&fs::read(&file)?) != entry.sha256 {
+                    say(lang, Message::FsckHashMismatch { asset, file: &file });
+                    problems += 1;
+                }
+                tracked.insert(file);
+            }
+            None => {
+                say(lang, Message::FsckMissingFile { asset });
+                problems += 1;
+                missing.insert(asset);
+                if repair {
+                    manifest.remove(asset);
+                }
+            }
+        }
+        for version in &entry.versions {
+            if !work_dir.join(&version.file).exists() {
+                say(
+                    lang,
+                    Message::FsckMissingVersion {
+                        asset,
+                        file: &version.file,
+                    },
+                );
+                problems += 1;
+                if repair {
+                    manifest.remove_version(asset, &version.file);
+                }
+            }
+        }
+    }
 
-    // Check if the request was successful
-    if response.status().is_success() {
-        // Parse the JSON response into your struct
-        let parsed_data: CollectionsResponse = response.json().await?;
-        let id_vec = parsed_data.data.iter().map(|de| de.collection_id.clone());
-        let set_data: HashSet<String> = id_vec.into_iter().collect();
-        return Ok(set_data);
-    } else {
-        return Ok(HashSet::new());
+    //the aliases of the covers found missing above don't have a cover either, repairing or not
+    for (asset, entry) in &entries {
+        let of = match &entry.alias_of {
+            Some(of) => of,
+            None => continue,
+        };
+        let resolves = !missing.contains(of.as_str())
+            && manifest
+                .get(of)
+                .is_some_and(|target| target.cid == entry.cid && target.alias_of.is_none());
+        if !resolves {
+            say(lang, Message::FsckDanglingAlias { asset, of });
+            problems += 1;
+            if repair {
+                manifest.remove(asset);
+            }
+        }
+    }
+
+    //cover files are named by asset id, with the extension of their format or none
+    for dir in [work_dir.to_path_buf(), work_dir.join(QUARANTINE_DIR)] {
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let file = entry?.path();
+            let cover_like = storage::image_extension(&file).is_some()
+                || file
+                    .file_name()
+                    .is_some_and(|name| !name.to_string_lossy().contains('.'));
+            if file.is_file() && cover_like && !tracked.contains(&file) {
+                say(lang, Message::FsckUntracked { file: &file });
+                problems += 1;
+            }
+        }
+    }
+
+    if repair {
+        manifest.compact(work_dir).await?;
+    }
+    say(
+        lang,
+        Message::FsckDone {
+            problems,
+            repaired: repair,
+        },
+    );
+    Ok(problems)
+}
+
+/// the path with `suffix` appended to the file name
+fn with_suffix(file: &Path, suffix: &str) -> PathBuf {
+    let mut path = file.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+/// prints the asset's onchain metadata, either raw or the parts the high-res cover is picked from
+async fn print_metadata(
+    fetcher: &CoverFetcher,
+    asset_id: &str,
+    raw: bool,
+) -> Result<(), Box<dyn Error>> {
+    let asset_details = fetcher.api().assets_by_id(asset_id).await?;
+    if raw {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&asset_details.onchain_metadata)?
+        );
+        return Ok(());
+    }
+
+    println!("asset: {}", asset_details.asset);
+    println!("policy id: {}", asset_details.policy_id);
+    if let Some(asset_name) = &asset_details.asset_name {
+        match decode_asset_name(asset_name) {
+            Some(decoded) => println!("asset name: {} ({})", decoded, asset_name),
+            None => println!("asset name: {}", asset_name),
+        }
+    }
+    let metadata = asset_details
+        .onchain_metadata
+        .as_ref()
+        .and_then(BookMetadata::from_value);
+    if let Some(metadata) = metadata.filter(|metadata| !metadata.authors.is_empty()) {
+        println!("authors: {}", metadata.authors.join(", "));
+    }
+    match get_high_res_cover(asset_details) {
+        Some(cover) => {
+            println!("name: {}", cover.name);
+            println!(
+                "selected file: {} ({})",
+                cover.path,
+                cover.media_type.as_deref().unwrap_or("no media type")
+            );
+            println!("cid: {}", cover.cid);
+        }
+        None => println!(
+            "no high-res cover: the metadata has no `files`, or the first one has no `src`"
+        ),
+    }
+    Ok(())
+}
+
+/// prints the verification result of each asset's cover
+async fn verify_covers(
+    lang: Lang,
+    fetcher: &CoverFetcher,
+    policy_id: &str,
+    work_dir: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut events = Box::pin(fetcher.verify_collection(policy_id, Path::new(work_dir)));
+    while let Some(event) = events.next().await {
+        let event = event.map_err(|err| err as Box<dyn Error>)?;
+        let message = match &event {
+            VerifyEvent::Ok { asset } => Message::VerifyOk { asset },
+            VerifyEvent::Missing { asset } => Message::VerifyMissing { asset },
+            VerifyEvent::HashMismatch {
+                asset,
+                expected,
+                actual,
+            } => Message::VerifyMismatch {
+                asset,
+                expected,
+                actual,
+            },
+            VerifyEvent::Unreadable { asset, error } => Message::VerifyUnreadable { asset, error },
+        };
+        say(lang, message);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assets(count: usize) -> Vec<AssetPolicy> {
+        (0..count)
+            .map(|i| AssetPolicy {
+                asset: format!("asset{}", i),
+                quantity: "1".to_owned(),
+            })
+            .collect()
+    }
+
+    /// downloads (with the number of assets), skips (`None`) or fails
+    fn follow(
+        onchain: usize,
+        in_catalog: Option<bool>,
+        trust: Option<Trust>,
+    ) -> Result<Option<usize>, String> {
+        let catalog = in_catalog.map(|listed| {
+            let mut ids = HashSet::new();
+            if listed {
+                ids.insert("policy".to_owned());
+            }
+            ids
+        });
+        follow_sources(Lang::En, assets(onchain), &catalog, "policy", trust)
+            .map(|assets| assets.map(|assets| assets.len()))
+            .map_err(|err| err.to_string())
+    }
+
+    #[tokio::test]
+    async fn fsck_reports_the_same_problems_with_or_without_repair() {
+        let work_dir = std::env::temp_dir().join(format!("book_cli-fsck-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&work_dir);
+        fs::create_dir_all(&work_dir).unwrap();
+        //asset1 lost its file, asset2 shares its cover
+        let mut manifest = Manifest::load(&work_dir).unwrap();
+        manifest.record("asset1", "QmCover", "asset1.png", b"cover");
+        manifest.record_alias("asset2", "QmCover", "asset1");
+        manifest.save(&work_dir).await.unwrap();
+
+        assert_eq!(fsck(Lang::En, &work_dir, false).await.unwrap(), 2);
+        assert_eq!(fsck(Lang::En, &work_dir, true).await.unwrap(), 2);
+        assert!(Manifest::load(&work_dir).unwrap().covers().is_empty());
+        fs::remove_dir_all(&work_dir).unwrap();
+    }
+
+    #[test]
+    fn both_sources_agree() {
+        for trust in [None, Some(Trust::Chain), Some(Trust::Catalog)] {
+            assert_eq!(follow(2, Some(true), trust), Ok(Some(2)));
+            assert_eq!(follow(0, Some(false), trust), Ok(None));
+        }
+    }
+
+    #[test]
+    fn without_catalog_any_policy_is_downloaded() {
+        assert_eq!(follow(2, None, None), Ok(Some(2)));
+        assert_eq!(follow(0, None, Some(Trust::Catalog)), Ok(Some(0)));
+    }
+
+    #[test]
+    fn only_onchain_follows_the_chain_if_trusted() {
+        assert_eq!(follow(2, Some(false), None), Ok(None));
+        assert_eq!(follow(2, Some(false), Some(Trust::Chain)), Ok(Some(2)));
+        assert_eq!(follow(2, Some(false), Some(Trust::Catalog)), Ok(None));
+    }
+
+    #[test]
+    fn only_in_catalog_fails_if_trusted() {
+        assert_eq!(follow(0, Some(true), None), Ok(None));
+        assert_eq!(follow(0, Some(true), Some(Trust::Chain)), Ok(None));
+        assert!(follow(0, Some(true), Some(Trust::Catalog)).is_err());
+    }
+
+    #[test]
+    fn adopting_never_overwrites_a_file() {
+        let dir = std::env::temp_dir().join(format!("book_cli-adopt-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("found.png"), b"found").unwrap();
+        fs::write(dir.join("asset1.png"), b"other").unwrap();
+
+        let err = link_or_copy(&dir.join("found.png"), &dir.join("asset1.png")).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(dir.join("asset1.png")).unwrap(), b"other");
+
+        link_or_copy(&dir.join("found.png"), &dir.join("asset2.png")).unwrap();
+        assert_eq!(fs::read(dir.join("asset2.png")).unwrap(), b"found");
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
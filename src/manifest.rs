@@ -0,0 +1,564 @@
+use crate::{receipt::sha256_hex, storage, timestamp};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// directory of the sharded layout, with an `index.json` and one `<shard>.json` per hash prefix
+pub const MANIFEST_DIR: &str = "manifest";
+const INDEX_FILE: &str = "index.json";
+
+/// How the manifest is stored in the work dir
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ManifestLayout {
+    /// a single `manifest.json`, rewritten on every change
+    #[default]
+    Single,
+    /// `manifest/<shard>.json` files by asset id hash prefix plus `manifest/index.json`,
+    /// only the changed shards are rewritten (for archives with a very large number of covers)
+    Sharded,
+}
+
+impl ManifestLayout {
+    pub fn parse(layout: &str) -> Option<ManifestLayout> {
+        match layout {
+            "single" => Some(ManifestLayout::Single),
+            "sharded" => Some(ManifestLayout::Sharded),
+            _ => None,
+        }
+    }
+}
+
+/// Record of the covers downloaded to a work dir, by asset id.
+/// Reading and writing are the same for both layouts, see [`ManifestLayout`]
+#[derive(Debug, Default)]
+pub struct Manifest {
+    covers: BTreeMap<String, ManifestEntry>,
+    layout: ManifestLayout,
+    // shards changed since loaded, all of them when the layout changed
+    dirty_shards: BTreeSet<String>,
+    converted: bool,
+}
+
+/// What was downloaded for an asset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub cid: String,
+    /// name of the cover file in the work dir (older manifests don't have it, the file is named by asset id)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// hex encoded sha2-256 of the content
+    pub sha256: String,
+    pub downloaded_at: String,
+    /// copies of the asset in circulation, when last seen
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<u64>,
+    /// how the cover doesn't conform to the cover policy, if it doesn't
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<String>,
+    /// previous covers of the asset, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<SupersededCover>,
+    /// asset with the same cover, whose file this one shares (there's no file of its own)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias_of: Option<String>,
+}
+
+/// A cover replaced by a newer one, kept in the `versions/` area
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupersededCover {
+    pub cid: String,
+    pub sha256: String,
+    pub downloaded_at: String,
+    pub superseded_at: String,
+    /// path of the archived file, relative to the work dir (`versions/<name>`)
+    pub file: String,
+}
+
+// content of `manifest.json` and of each shard
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ManifestFile {
+    covers: BTreeMap<String, ManifestEntry>,
+}
+
+// content of `manifest/index.json`: number of covers by shard
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ShardIndex {
+    shards: BTreeMap<String, usize>,
+}
+
+/// shard of an asset, the first byte of its id hash (so collections spread over all the shards)
+fn shard_of(asset: &str) -> String {
+    sha256_hex(asset.as_bytes())[..2].to_owned()
+}
+
+/// path of an archived cover relative to the work dir, from its path (relative to wherever)
+fn versions_file(file: &Path) -> String {
+    let name = file.file_name().unwrap_or(file.as_os_str());
+    format!("{}/{}", storage::VERSIONS_DIR, name.to_string_lossy())
+}
+
+// removing the files of the previous layout is fine if they were never written
+fn ignore_not_found(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+impl Manifest {
+    /// loads the work dir manifest in whatever layout it is, a missing manifest is an empty one
+    pub fn load(dir: &Path) -> Result<Manifest, Box<dyn Error>> {
+        let index_path = dir.join(MANIFEST_DIR).join(INDEX_FILE);
+        if index_path.exists() {
+            let index: ShardIndex = serde_json::from_slice(&fs::read(index_path)?)?;
+            let mut covers = BTreeMap::new();
+            for shard in index.shards.keys() {
+                let shard_path = dir.join(MANIFEST_DIR).join(format!("{}.json", shard));
+                let shard_file: ManifestFile = serde_json::from_slice(&fs::read(shard_path)?)?;
+                covers.extend(shard_file.covers);
+            }
+            let mut manifest = Manifest {
+                covers,
+                layout: ManifestLayout::Sharded,
+                ..Default::default()
+            };
+            manifest.relative_versions();
+            return Ok(manifest);
+        }
+
+        match fs::read(dir.join(MANIFEST_FILE)) {
+            Ok(content) => {
+                let manifest_file: ManifestFile = serde_json::from_slice(&content)?;
+                let mut manifest = Manifest {
+                    covers: manifest_file.covers,
+                    ..Default::default()
+                };
+                manifest.relative_versions();
+                Ok(manifest)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// older runs recorded the archived files relative to where they ran, they are all in `versions/`
+    fn relative_versions(&mut self) {
+        for (asset, entry) in self.covers.iter_mut() {
+            for version in entry.versions.iter_mut() {
+                let relative = versions_file(Path::new(&version.file));
+                if version.file != relative {
+                    version.file = relative;
+                    self.dirty_shards.insert(shard_of(asset));
+                }
+            }
+        }
+    }
+
+    pub fn layout(&self) -> ManifestLayout {
+        self.layout
+    }
+
+    /// changes the layout, the whole manifest is written in the new one on the next save
+    pub fn set_layout(&mut self, layout: ManifestLayout) {
+        if layout != self.layout {
+            self.layout = layout;
+            self.converted = true;
+            self.dirty_shards = self.covers.keys().map(|asset| shard_of(asset)).collect();
+        }
+    }
+
+    /// writes the manifest (through temp files, so it's never left half written):
+    /// the whole `manifest.json`, or only the changed shards and the index
+    pub async fn save(&mut self, dir: &Path) -> io::Result<()> {
+        match self.layout {
+            ManifestLayout::Single => {
+                let manifest_file = ManifestFile {
+                    covers: self.covers.clone(),
+                };
+                let content = serde_json::to_vec_pretty(&manifest_file)?;
+                storage::write_file(&dir.join(MANIFEST_FILE).to_string_lossy(), &content).await?;
+                if self.converted {
+                    ignore_not_found(fs::remove_dir_all(dir.join(MANIFEST_DIR)))?;
+                }
+            }
+            ManifestLayout::Sharded => {
+                let manifest_dir = dir.join(MANIFEST_DIR);
+                fs::create_dir_all(&manifest_dir)?;
+
+                let mut index = ShardIndex::default();
+                let mut dirty: BTreeMap<String, ManifestFile> = BTreeMap::new();
+                for (asset, entry) in &self.covers {
+                    let shard = shard_of(asset);
+                    if self.dirty_shards.contains(&shard) {
+                        let shard_file = dirty.entry(shard.clone()).or_default();
+                        shard_file.covers.insert(asset.clone(), entry.clone());
+                    }
+                    *index.shards.entry(shard).or_default() += 1;
+                }
+                for (shard, shard_file) in dirty {
+                    let content = serde_json::to_vec_pretty(&shard_file)?;
+                    let shard_path = manifest_dir.join(format!("{}.json", shard));
+                    storage::write_file(&shard_path.to_string_lossy(), &content).await?;
+                }
+
+                //the index is written last, so it never lists a shard that doesn't exist yet
+                let content = serde_json::to_vec_pretty(&index)?;
+                let index_path = manifest_dir.join(INDEX_FILE);
+                storage::write_file(&index_path.to_string_lossy(), &content).await?;
+                if self.converted {
+                    ignore_not_found(fs::remove_file(dir.join(MANIFEST_FILE)))?;
+                }
+            }
+        }
+        self.dirty_shards.clear();
+        self.converted = false;
+        Ok(())
+    }
+
+    pub fn get(&self, asset: &str) -> Option<&ManifestEntry> {
+        self.covers.get(asset)
+    }
+
+    /// all the recorded covers, by asset id
+    pub fn covers(&self) -> &BTreeMap<String, ManifestEntry> {
+        &self.covers
+    }
+
+    /// records the content downloaded for an asset, replacing the current entry but keeping its versions,
+    /// and when it was first downloaded if the cid is the same (a changed cid goes through `supersede` first)
+    pub fn record(&mut self, asset: &str, cid: &str, file: &str, data: &[u8]) {
+        let (versions, downloaded_at) = self.take_history(asset, cid);
+        self.covers.insert(
+            asset.to_owned(),
+            ManifestEntry {
+                cid: cid.to_owned(),
+                file: Some(file.to_owned()),
+                sha256: sha256_hex(data),
+                downloaded_at,
+                quantity: None,
+                violations: Vec::new(),
+                versions,
+                alias_of: None,
+            },
+        );
+        self.dirty_shards.insert(shard_of(asset));
+    }
+
+    /// records an asset whose cover is the same as the one of an asset already recorded, `of`
+    pub fn record_alias(&mut self, asset: &str, cid: &str, of: &str) {
+        let sha256 = match self.covers.get(of) {
+            Some(entry) => entry.sha256.clone(),
+            None => return,
+        };
+        let (versions, downloaded_at) = self.take_history(asset, cid);
+        self.covers.insert(
+            asset.to_owned(),
+            ManifestEntry {
+                cid: cid.to_owned(),
+                file: None,
+                sha256,
+                downloaded_at,
+                quantity: None,
+                violations: Vec::new(),
+                versions,
+                alias_of: Some(of.to_owned()),
+            },
+        );
+        self.dirty_shards.insert(shard_of(asset));
+    }
+
+    /// removes the entry of an asset, returning its versions and its download time if it's for `cid`
+    /// (otherwise now)
+    fn take_history(&mut self, asset: &str, cid: &str) -> (Vec<SupersededCover>, String) {
+        match self.covers.remove(asset) {
+            Some(entry) if entry.cid == cid => (entry.versions, entry.downloaded_at),
+            Some(entry) => (entry.versions, timestamp::now()),
+            None => (Vec::new(), timestamp::now()),
+        }
+    }
+
+    /// The covers the work dir had at `at` (an RFC 3339 UTC timestamp, as recorded), as asset id to cid:
+    /// the current cover if it was downloaded by then, otherwise the version that was current at the time
+    pub fn covers_at(&self, at: &str) -> BTreeMap<&str, &str> {
+        self.covers
+            .iter()
+            .filter_map(|(asset, entry)| {
+                if entry.downloaded_at.as_str() <= at {
+                    return Some((asset.as_str(), entry.cid.as_str()));
+                }
+                entry
+                    .versions
+                    .iter()
+                    .find(|version| {
+                        version.downloaded_at.as_str() <= at && at < version.superseded_at.as_str()
+                    })
+                    .map(|version| (asset.as_str(), version.cid.as_str()))
+            })
+            .collect()
+    }
+
+    /// asset recorded with its own file for a cid, if any
+    pub fn find_by_cid(&self, cid: &str) -> Option<&str> {
+        self.covers
+            .iter()
+            .find(|(_, entry)| entry.cid == cid && entry.alias_of.is_none())
+            .map(|(asset, _)| asset.as_str())
+    }
+
+    /// updates the asset quantity, if it changed
+    pub fn set_quantity(&mut self, asset: &str, quantity: u64) {
+        if let Some(entry) = self.covers.get_mut(asset) {
+            if entry.quantity != Some(quantity) {
+                entry.quantity = Some(quantity);
+                self.dirty_shards.insert(shard_of(asset));
+            }
+        }
+    }
+
+    /// true if there are changes not saved yet
+    pub fn has_changes(&self) -> bool {
+        !self.dirty_shards.is_empty()
+    }
+
+    /// flags the current cover of an asset as not conforming to the cover policy
+    pub fn set_violations(&mut self, asset: &str, violations: Vec<String>) {
+        if let Some(entry) = self.covers.get_mut(asset) {
+            entry.violations = violations;
+            self.dirty_shards.insert(shard_of(asset));
+        }
+    }
+
+    /// path of the current cover file of an asset: the one recorded if it's still there
+    /// (e.g. in quarantine), otherwise the asset file in the work dir, with or without extension.
+    /// Aliases resolve to the file of the asset they share it with, while it still has the same cid
+    pub fn cover_file(&self, work_dir: &Path, asset: &str) -> Option<PathBuf> {
+        let mut asset = asset;
+        let mut entry = self.get(asset);
+        if let Some((alias, of)) = entry.and_then(|entry| Some((entry, entry.alias_of.as_deref()?)))
+        {
+            match self.get(of) {
+                Some(target) if target.cid == alias.cid && target.alias_of.is_none() => {
+                    asset = of;
+                    entry = Some(target);
+                }
+                _ => return None,
+            }
+        }
+        entry
+            .and_then(|entry| entry.file.as_ref())
+            .map(|file| work_dir.join(file))
+            .filter(|file| file.exists())
+            .or_else(|| storage::find_cover(work_dir, asset))
+    }
+
+    /// updates the references to a cover file of the asset (current or superseded) after it was renamed
+    pub fn file_renamed(&mut self, asset: &str, from: &Path, to: &Path) {
+        let (from_name, to_name) = match (from.file_name(), to.file_name()) {
+            (Some(from_name), Some(to_name)) => (from_name, to_name),
+            _ => return,
+        };
+        if let Some(entry) = self.covers.get_mut(asset) {
+            if entry.file.as_deref().unwrap_or(asset) == from_name {
+                entry.file = Some(to_name.to_string_lossy().into_owned());
+            }
+            for version in entry.versions.iter_mut() {
+                let version_file = Path::new(&version.file);
+                if version_file.file_name() == Some(from_name) {
+                    version.file = version_file
+                        .with_file_name(to_name)
+                        .to_string_lossy()
+                        .into_owned();
+                }
+            }
+            self.dirty_shards.insert(shard_of(asset));
+        }
+    }
+
+    /// forgets an asset, e.g. one whose cover file is gone, so the next run downloads it again
+    pub fn remove(&mut self, asset: &str) -> Option<ManifestEntry> {
+        let entry = self.covers.remove(asset);
+        if entry.is_some() {
+            self.dirty_shards.insert(shard_of(asset));
+        }
+        entry
+    }
+
+    /// forgets a superseded cover of an asset, by its archived file
+    pub fn remove_version(&mut self, asset: &str, file: &str) {
+        if let Some(entry) = self.covers.get_mut(asset) {
+            entry.versions.retain(|version| version.file != file);
+            self.dirty_shards.insert(shard_of(asset));
+        }
+    }
+
+    /// writes the whole manifest again, rebuilding the shard index and removing the shards that
+    /// don't hold any cover anymore, returns how many were removed
+    pub async fn compact(&mut self, dir: &Path) -> io::Result<usize> {
+        self.dirty_shards = self.covers.keys().map(|asset| shard_of(asset)).collect();
+        self.save(dir).await?;
+
+        let manifest_dir = dir.join(MANIFEST_DIR);
+        if self.layout != ManifestLayout::Sharded || !manifest_dir.is_dir() {
+            return Ok(0);
+        }
+        let shards: BTreeSet<String> = self.covers.keys().map(|asset| shard_of(asset)).collect();
+        let mut removed = 0;
+        for entry in fs::read_dir(&manifest_dir)? {
+            let file = entry?.path();
+            let is_shard = file.extension() == Some("json".as_ref())
+                && file.file_name() != Some(INDEX_FILE.as_ref());
+            let shard = match file.file_stem() {
+                Some(shard) if is_shard => shard.to_string_lossy().into_owned(),
+                _ => continue,
+            };
+            if !shards.contains(&shard) {
+                fs::remove_file(file)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// adds the current cover of an asset to its versions, once archived to `file` in the `versions/` area
+    pub fn supersede(&mut self, asset: &str, file: &Path) {
+        if let Some(entry) = self.covers.get_mut(asset) {
+            entry.versions.push(SupersededCover {
+                cid: entry.cid.clone(),
+                sha256: entry.sha256.clone(),
+                downloaded_at: entry.downloaded_at.clone(),
+                superseded_at: timestamp::now(),
+                file: versions_file(file),
+            });
+            self.dirty_shards.insert(shard_of(asset));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASSETS: [&str; 3] = ["asset1", "asset2", "asset3"];
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("book_cli-manifest-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn recorded(assets: &[&str]) -> Manifest {
+        let mut manifest = Manifest::default();
+        for asset in assets {
+            manifest.record(asset, &format!("Qm{}", asset), asset, asset.as_bytes());
+        }
+        manifest
+    }
+
+    fn shard_files(dir: &Path) -> BTreeSet<String> {
+        fs::read_dir(dir.join(MANIFEST_DIR))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name != INDEX_FILE)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn converts_between_layouts() {
+        let dir = test_dir("layouts");
+        let mut manifest = recorded(&ASSETS);
+        manifest.save(&dir).await.unwrap();
+        assert!(dir.join(MANIFEST_FILE).is_file());
+
+        manifest.set_layout(ManifestLayout::Sharded);
+        manifest.save(&dir).await.unwrap();
+        assert!(!dir.join(MANIFEST_FILE).exists());
+        let mut sharded = Manifest::load(&dir).unwrap();
+        assert_eq!(sharded.layout(), ManifestLayout::Sharded);
+        assert_eq!(sharded.covers().keys().collect::<Vec<_>>(), ASSETS);
+        assert_eq!(shard_files(&dir).len(), ASSETS.len());
+
+        sharded.set_layout(ManifestLayout::Single);
+        sharded.save(&dir).await.unwrap();
+        assert!(!dir.join(MANIFEST_DIR).exists());
+        let single = Manifest::load(&dir).unwrap();
+        assert_eq!(single.layout(), ManifestLayout::Single);
+        assert_eq!(single.covers().keys().collect::<Vec<_>>(), ASSETS);
+        for asset in ASSETS {
+            assert_eq!(single.get(asset).unwrap().cid, format!("Qm{}", asset));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn saves_only_the_changed_shards() {
+        let dir = test_dir("dirty");
+        let mut manifest = recorded(&ASSETS[..2]);
+        manifest.set_layout(ManifestLayout::Sharded);
+        manifest.save(&dir).await.unwrap();
+        assert!(!manifest.has_changes());
+
+        //the shards written by the first save are gone, only the changed one is written again
+        for shard in shard_files(&dir) {
+            fs::remove_file(dir.join(MANIFEST_DIR).join(shard)).unwrap();
+        }
+        manifest.record(ASSETS[2], "QmNew", ASSETS[2], b"new");
+        manifest.save(&dir).await.unwrap();
+        let expected = BTreeSet::from([format!("{}.json", shard_of(ASSETS[2]))]);
+        assert_eq!(shard_files(&dir), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn covers_at_before_between_and_after_a_supersede() {
+        let mut manifest = recorded(&ASSETS[..1]);
+        let entry = manifest.covers.get_mut(ASSETS[0]).unwrap();
+        entry.downloaded_at = "2024-03-01T00:00:00Z".to_owned();
+        entry.versions.push(SupersededCover {
+            cid: "QmOld".to_owned(),
+            sha256: String::new(),
+            downloaded_at: "2024-01-01T00:00:00Z".to_owned(),
+            superseded_at: "2024-03-01T00:00:00Z".to_owned(),
+            file: "versions/asset1.png".to_owned(),
+        });
+        let cid_at = |date: &str| {
+            let at = timestamp::parse_point_in_time(date).unwrap();
+            manifest
+                .covers_at(&at)
+                .get(ASSETS[0])
+                .map(|cid| cid.to_string())
+        };
+
+        assert_eq!(cid_at("2023-12-31"), None);
+        assert_eq!(cid_at("2024-01-01T00:00:00Z").as_deref(), Some("QmOld"));
+        assert_eq!(cid_at("2024-02-01").as_deref(), Some("QmOld"));
+        assert_eq!(cid_at("2024-02-29T23:59:59Z").as_deref(), Some("QmOld"));
+        assert_eq!(cid_at("2024-03-01T00:00:00Z").as_deref(), Some("Qmasset1"));
+        assert_eq!(cid_at("2024-06-01").as_deref(), Some("Qmasset1"));
+    }
+
+    #[tokio::test]
+    async fn compact_removes_the_empty_shards() {
+        let dir = test_dir("compact");
+        let mut manifest = recorded(&ASSETS);
+        manifest.set_layout(ManifestLayout::Sharded);
+        manifest.save(&dir).await.unwrap();
+
+        manifest.remove(ASSETS[0]);
+        manifest.save(&dir).await.unwrap();
+        assert_eq!(shard_files(&dir).len(), ASSETS.len());
+        assert_eq!(manifest.compact(&dir).await.unwrap(), 1);
+        assert!(!shard_files(&dir).contains(&format!("{}.json", shard_of(ASSETS[0]))));
+        assert_eq!(shard_files(&dir).len(), ASSETS.len() - 1);
+
+        let loaded = Manifest::load(&dir).unwrap();
+        assert_eq!(loaded.covers().keys().collect::<Vec<_>>(), &ASSETS[1..]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+/// Onchain (CIP-25) metadata of a book asset. Parsed leniently, as minted metadata comes with quirks:
+/// long strings split in arrays of up to 64 byte chunks (`src`, `image`, `description`), the title as
+/// `name` or `title`, authors as names or `{ "name": ... }` objects under `authors` or `author`,
+/// `files` as a list or a single object, the version as a number or a string.
+/// Serializes normalized, in the CIP-25 field names.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BookMetadata {
+    #[serde(rename = "name", skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    /// thumbnail, usually an ipfs url
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(rename = "mediaType", skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// the high-res cover first, for book.io assets. Positional: entries without `src` are kept
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<BookFile>,
+    /// CIP-25 version as written, `None` if not set (which CIP-25 reads as `1.0`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// One of the `files` of the metadata
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BookFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "mediaType", skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    /// url of the file (chunks joined), e.g. `ipfs://<cid>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub src: Option<String>,
+}
+
+impl BookMetadata {
+    /// parses the metadata json, `None` if it isn't an object
+    pub fn from_value(value: &Value) -> Option<BookMetadata> {
+        let fields = value.as_object()?;
+        let files = match fields.get("files") {
+            Some(Value::Array(files)) => files
+                .iter()
+                .map(|file| BookFile::from_value(file).unwrap_or_default())
+                .collect(),
+            Some(file) => BookFile::from_value(file).into_iter().collect(),
+            None => Vec::new(),
+        };
+        Some(BookMetadata {
+            title: text(fields, "name").or_else(|| text(fields, "title")),
+            authors: authors(fields.get("authors").or_else(|| fields.get("author"))),
+            image: text(fields, "image"),
+            media_type: text(fields, "mediaType"),
+            description: text(fields, "description"),
+            files,
+            version: text(fields, "version"),
+        })
+    }
+}
+
+impl BookFile {
+    /// parses a file entry, `None` if it isn't an object
+    pub fn from_value(value: &Value) -> Option<BookFile> {
+        let fields = value.as_object()?;
+        Some(BookFile {
+            name: text(fields, "name"),
+            media_type: text(fields, "mediaType"),
+            src: text(fields, "src"),
+        })
+    }
+
+    /// the ipfs cid (and path, if any) of the file, from `ipfs://<cid>`, `ipfs://ipfs/<cid>`,
+    /// gateway urls (`https://<gateway>/ipfs/<cid>`) or a bare cid
+    pub fn cid(&self) -> Option<&str> {
+        let src = self.src.as_deref()?.trim();
+        if let Some(cid) = src.strip_prefix("ipfs://") {
+            return Some(cid.strip_prefix("ipfs/").unwrap_or(cid));
+        }
+        if let Some((_, cid)) = src.split_once("/ipfs/") {
+            return Some(cid);
+        }
+        if src.starts_with("Qm") || src.starts_with("baf") {
+            return Some(src);
+        }
+        None
+    }
+}
+
+impl<'de> Deserialize<'de> for BookMetadata {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        BookMetadata::from_value(&value)
+            .ok_or_else(|| de::Error::custom("expected a metadata object"))
+    }
+}
+
+impl<'de> Deserialize<'de> for BookFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        BookFile::from_value(&value).ok_or_else(|| de::Error::custom("expected a file object"))
+    }
+}
+
+/// a text field, joining the chunks of the long ones, numbers as written
+fn text(fields: &Map<String, Value>, key: &str) -> Option<String> {
+    match fields.get(key)? {
+        Value::String(text) => Some(text.to_owned()),
+        Value::Array(chunks) => chunks
+            .iter()
+            .map(|chunk| chunk.as_str())
+            .collect::<Option<String>>(),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// author names, from a name, a `{ "name": ... }` object, or a list of them
+fn authors(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::String(name)) => vec![name.to_owned()],
+        Some(Value::Object(author)) => text(author, "name").into_iter().collect(),
+        Some(Value::Array(list)) => list
+            .iter()
+            .flat_map(|author| authors(Some(author)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn chunked_strings_are_joined() {
+        let metadata = BookMetadata::from_value(&json!({
+            "image": ["ipfs://QmThumb", "nail"],
+            "description": ["A long ", "description"],
+            "files": [{ "src": ["ipfs://QmCo", "ver"] }],
+        }))
+        .unwrap();
+        assert_eq!(metadata.image.as_deref(), Some("ipfs://QmThumbnail"));
+        assert_eq!(metadata.description.as_deref(), Some("A long description"));
+        assert_eq!(metadata.files[0].src.as_deref(), Some("ipfs://QmCover"));
+        assert_eq!(metadata.files[0].cid(), Some("QmCover"));
+    }
+
+    #[test]
+    fn title_from_name_or_title() {
+        let name = BookMetadata::from_value(&json!({ "name": "Dune", "title": "Other" })).unwrap();
+        assert_eq!(name.title.as_deref(), Some("Dune"));
+        let title = BookMetadata::from_value(&json!({ "title": "Dune" })).unwrap();
+        assert_eq!(title.title.as_deref(), Some("Dune"));
+    }
+
+    #[test]
+    fn authors_as_names_or_objects() {
+        let authors = |metadata: Value| BookMetadata::from_value(&metadata).unwrap().authors;
+        assert_eq!(
+            authors(json!({ "authors": ["Ann", "Bob"] })),
+            ["Ann", "Bob"]
+        );
+        assert_eq!(authors(json!({ "author": "Ann" })), ["Ann"]);
+        assert_eq!(
+            authors(json!({ "authors": [{ "name": "Ann" }, { "name": ["B", "ob"] }] })),
+            ["Ann", "Bob"]
+        );
+        assert_eq!(authors(json!({ "author": { "name": "Ann" } })), ["Ann"]);
+        assert!(authors(json!({})).is_empty());
+    }
+
+    #[test]
+    fn files_as_a_list_or_an_object() {
+        let list = BookMetadata::from_value(&json!({
+            "files": [{ "src": "ipfs://QmCover", "mediaType": "image/png" }, { "src": "ipfs://QmEpub" }],
+        }))
+        .unwrap();
+        assert_eq!(list.files.len(), 2);
+        assert_eq!(list.files[0].media_type.as_deref(), Some("image/png"));
+        let object =
+            BookMetadata::from_value(&json!({ "files": { "src": "ipfs://QmCover" } })).unwrap();
+        assert_eq!(object.files.len(), 1);
+        assert_eq!(object.files[0].cid(), Some("QmCover"));
+    }
+
+    #[test]
+    fn files_keep_their_positions() {
+        let metadata = BookMetadata::from_value(&json!({
+            "files": [{ "name": "cover" }, "not a file", { "src": "ipfs://QmEpub" }],
+        }))
+        .unwrap();
+        assert_eq!(metadata.files.len(), 3);
+        assert_eq!(metadata.files[0].src, None);
+        assert_eq!(metadata.files[0].cid(), None);
+        assert_eq!(metadata.files[1], BookFile::default());
+        assert_eq!(metadata.files[2].cid(), Some("QmEpub"));
+    }
+
+    #[test]
+    fn version_as_a_number_or_a_string() {
+        let version = |metadata: Value| BookMetadata::from_value(&metadata).unwrap().version;
+        assert_eq!(version(json!({ "version": 2 })).as_deref(), Some("2"));
+        assert_eq!(version(json!({ "version": 1.5 })).as_deref(), Some("1.5"));
+        assert_eq!(version(json!({ "version": "1.0" })).as_deref(), Some("1.0"));
+        assert_eq!(version(json!({})), None);
+    }
+}
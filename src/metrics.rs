@@ -0,0 +1,90 @@
+use crate::{storage, timestamp, RunCounts};
+use std::{fmt::Write, io, path::Path};
+
+/// Prometheus text snapshot of a run's counters, for node_exporter's textfile collector. They are
+/// gauges: each run starts them over, and the textfile collector reads the Prometheus text format,
+/// which has a counter's samples named as its family (no `_total` suffix as in OpenMetrics)
+pub fn openmetrics(counts: &RunCounts, finished_at: u64) -> String {
+    let mut text = String::new();
+    text.push_str("# HELP book_cli_covers Covers handled in the last run, by outcome.\n");
+    text.push_str("# TYPE book_cli_covers gauge\n");
+    for (outcome, value) in counts.outcomes() {
+        let _ = writeln!(text, "book_cli_covers{{outcome=\"{}\"}} {}", outcome, value);
+    }
+    text.push_str(
+        "# HELP book_cli_transferred_bytes Bytes downloaded from the gateway in the last run, by kind.\n",
+    );
+    text.push_str("# TYPE book_cli_transferred_bytes gauge\n");
+    for (kind, value) in counts.transferred() {
+        let _ = writeln!(
+            text,
+            "book_cli_transferred_bytes{{kind=\"{}\"}} {}",
+            kind, value
+        );
+    }
+    text.push_str(
+        "# HELP book_cli_stalled_transfers Transfers aborted for going below the minimum throughput in the last run.\n",
+    );
+    text.push_str("# TYPE book_cli_stalled_transfers gauge\n");
+    let _ = writeln!(
+        text,
+        "book_cli_stalled_transfers {}",
+        counts.stalled_transfers
+    );
+    text.push_str("# HELP book_cli_last_run_timestamp_seconds When the last run finished.\n");
+    text.push_str("# TYPE book_cli_last_run_timestamp_seconds gauge\n");
+    let _ = writeln!(text, "book_cli_last_run_timestamp_seconds {}", finished_at);
+    text.push_str("# EOF\n");
+    text
+}
+
+/// writes the snapshot through a temp file, so the collector never scrapes a partial one
+pub async fn write_metrics(path: &Path, counts: &RunCounts) -> io::Result<()> {
+    let text = openmetrics(counts, timestamp::unix_now());
+    storage::write_file(&path.to_string_lossy(), text.as_bytes()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_text() {
+        let counts = RunCounts {
+            downloaded: 3,
+            already_downloaded: 2,
+            download_failed: 1,
+            useful_bytes: 3000,
+            failed_bytes: 120,
+            stalled_transfers: 1,
+            ..RunCounts::default()
+        };
+        assert_eq!(
+            openmetrics(&counts, 1704103200),
+            r#"# HELP book_cli_covers Covers handled in the last run, by outcome.
+# TYPE book_cli_covers gauge
+book_cli_covers{outcome="downloaded"} 3
+book_cli_covers{outcome="superseded"} 0
+book_cli_covers{outcome="already_downloaded"} 2
+book_cli_covers{outcome="duplicate"} 0
+book_cli_covers{outcome="no_cover"} 0
+book_cli_covers{outcome="expected_cid_mismatch"} 0
+book_cli_covers{outcome="cover_policy_violation"} 0
+book_cli_covers{outcome="claimed_by_other_worker"} 0
+book_cli_covers{outcome="download_failed"} 1
+# HELP book_cli_transferred_bytes Bytes downloaded from the gateway in the last run, by kind.
+# TYPE book_cli_transferred_bytes gauge
+book_cli_transferred_bytes{kind="useful"} 3000
+book_cli_transferred_bytes{kind="duplicate"} 0
+book_cli_transferred_bytes{kind="failed"} 120
+# HELP book_cli_stalled_transfers Transfers aborted for going below the minimum throughput in the last run.
+# TYPE book_cli_stalled_transfers gauge
+book_cli_stalled_transfers 1
+# HELP book_cli_last_run_timestamp_seconds When the last run finished.
+# TYPE book_cli_last_run_timestamp_seconds gauge
+book_cli_last_run_timestamp_seconds 1704103200
+# EOF
+"#
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use crate::Onchain;
+use blockfrost::{AssetDetails, AssetPolicy};
+use bytes::Bytes;
+use futures::{future::BoxFuture, FutureExt};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+/// In-process stand-in for the book.io collections api, Blockfrost and an ipfs gateway, so code embedding the
+/// library can be tested without network access. Serves on a local port until dropped
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// let mock = book_cli::mock::MockServer::start().await?;
+/// mock.add_collection("<policy id>");
+/// mock.add_content("<cid>", b"<cover>".to_vec());
+/// let catalog = book_cli::CollectionSource::Url(mock.collections_url());
+/// let gateway = mock.gateway_url();
+/// # Ok(())
+/// # }
+/// ```
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    server: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct State {
+    collections: BTreeSet<String>,
+    contents: HashMap<String, Bytes>,
+    //gateway requests to fail, by cid
+    failures: HashMap<String, u32>,
+    //gateway requests received, by cid
+    requests: HashMap<String, u32>,
+    //bodies delimited by closing the connection instead
+    without_content_length: bool,
+    //rate the bodies are sent at, all at once if not set
+    body_bytes_per_second: Option<u64>,
+    //onchain metadata of the assets, by policy id and asset id
+    assets: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+impl MockServer {
+    /// starts serving on a free local port
+    pub async fn start() -> io::Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let server = tokio::spawn(serve(listener, state.clone()));
+        Ok(MockServer {
+            addr,
+            state,
+            server,
+        })
+    }
+
+    /// url of the book.io compatible collections api, for [`crate::CollectionSource::Url`]
+    pub fn collections_url(&self) -> String {
+        format!("http://{}/api/v0/collections", self.addr)
+    }
+
+    /// url of the gateway, for [`crate::CoverFetcher::new`]
+    pub fn gateway_url(&self) -> String {
+        format!("http://{}/ipfs/", self.addr)
+    }
+
+    /// lists a collection in the catalog
+    pub fn add_collection(&self, policy_id: &str) {
+        self.state().collections.insert(policy_id.to_owned());
+    }
+
+    /// serves `content` for a cid in the gateway
+    pub fn add_content(&self, cid: &str, content: impl Into<Bytes>) {
+        self.state().contents.insert(cid.to_owned(), content.into());
+    }
+
+    /// the next `times` gateway requests of a cid fail with 503, e.g. to test retries
+    pub fn fail_next(&self, cid: &str, times: u32) {
+        self.state().failures.insert(cid.to_owned(), times);
+    }
+
+    /// responses are sent without `Content-Length`, the body ending when the connection is closed
+    /// (as some gateways stream them), so incomplete ones can't be told from the header
+    pub fn without_content_length(&self) {
+        self.state().without_content_length = true;
+    }
+
+    /// response bodies are sent at `bytes_per_second` (a tenth of it every 100ms) instead of all at
+    /// once, e.g. to test the minimum throughput of transfers
+    pub fn slow_body(&self, bytes_per_second: u64) {
+        self.state().body_bytes_per_second = Some(bytes_per_second);
+    }
+
+    /// mints an asset with its onchain (CIP-25) metadata, served by [`MockServer::onchain`].
+    /// Its policy id is the first 56 characters of the asset id
+    pub fn add_asset(&self, asset: &str, metadata: Value) {
+        let policy_id = asset.get(..56).unwrap_or(asset);
+        self.state()
+            .assets
+            .entry(policy_id.to_owned())
+            .or_default()
+            .insert(asset.to_owned(), metadata);
+    }
+
+    /// stand-in for Blockfrost serving the assets added, for [`crate::CoverFetcher::with_onchain`]
+    pub fn onchain(&self) -> MockOnchain {
+        MockOnchain {
+            state: self.state.clone(),
+        }
+    }
+
+    /// gateway requests received for a cid, failed ones included
+    pub fn requests(&self, cid: &str) -> u32 {
+        self.state().requests.get(cid).copied().unwrap_or(0)
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// The assets of a [`MockServer`], as Blockfrost would answer for them: an unknown policy has
+/// no assets, an unknown asset no metadata
+pub struct MockOnchain {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockOnchain {
+    fn policy_assets(&self, policy_id: &str) -> BTreeMap<String, Value> {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.assets.get(policy_id).cloned().unwrap_or_default()
+    }
+}
+
+impl Onchain for MockOnchain {
+    fn assets_policy_by_id<'a>(
+        &'a self,
+        policy_id: &'a str,
+    ) -> BoxFuture<'a, blockfrost::Result<Vec<AssetPolicy>>> {
+        let assets = self
+            .policy_assets(policy_id)
+            .into_keys()
+            .map(|asset| AssetPolicy {
+                asset,
+                quantity: "1".to_owned(),
+            })
+            .collect();
+        futures::future::ok(assets).boxed()
+    }
+
+    fn assets_by_id<'a>(
+        &'a self,
+        asset: &'a str,
+    ) -> BoxFuture<'a, blockfrost::Result<AssetDetails>> {
+        let policy_id = asset.get(..56).unwrap_or(asset);
+        let metadata = self.policy_assets(policy_id).remove(asset);
+        let details = json!({
+            "asset": asset,
+            "policy_id": policy_id,
+            "asset_name": asset.get(56..),
+            "fingerprint": "",
+            "quantity": "1",
+            "initial_mint_tx_hash": "",
+            "mint_or_burn_count": 1,
+            "onchain_metadata": metadata,
+        });
+        let details =
+            serde_json::from_value(details).expect("asset details as Blockfrost has them");
+        futures::future::ok(details).boxed()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, state: Arc<Mutex<State>>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(handle(stream, state.clone()));
+    }
+}
+
+/// answers a single request per connection, closing it afterwards
+async fn handle(mut stream: TcpStream, state: Arc<Mutex<State>>) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.windows(4).any(|end| end == b"\r\n\r\n") && request.len() < 16 * 1024 {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    //`GET <path> HTTP/1.1`
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status, content_type, body, content_length, body_bytes_per_second) = {
+        let mut state = state.lock().unwrap_or_else(|err| err.into_inner());
+        let (status, content_type, body) = respond(&mut state, path);
+        let content_length = match state.without_content_length {
+            true => String::new(),
+            false => format!("Content-Length: {}\r\n", body.len()),
+        };
+        (
+            status,
+            content_type,
+            body,
+            content_length,
+            state.body_bytes_per_second,
+        )
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\n{}Connection: close\r\n\r\n",
+        status, content_type, content_length
+    );
+    stream.write_all(head.as_bytes()).await?;
+    match body_bytes_per_second {
+        Some(bytes_per_second) => {
+            for chunk in body.chunks((bytes_per_second / 10).max(1) as usize) {
+                stream.write_all(chunk).await?;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+        None => stream.write_all(&body).await?,
+    }
+    stream.shutdown().await
+}
+
+fn respond(state: &mut State, path: &str) -> (&'static str, &'static str, Bytes) {
+    if path == "/api/v0/collections" {
+        let data: Vec<_> = state
+            .collections
+            .iter()
+            .map(|policy_id| {
+                json!({
+                    "collection_id": policy_id,
+                    "description": "",
+                    "blockchain": "cardano",
+                    "network": "mainnet",
+                })
+            })
+            .collect();
+        let body = json!({ "type": "collections", "data": data }).to_string();
+        return ("200 OK", "application/json", Bytes::from(body));
+    }
+
+    let cid = match path.strip_prefix("/ipfs/") {
+        Some(cid) => cid,
+        None => return ("404 Not Found", "text/plain", Bytes::new()),
+    };
+    *state.requests.entry(cid.to_owned()).or_default() += 1;
+    if let Some(failures) = state
+        .failures
+        .get_mut(cid)
+        .filter(|failures| **failures > 0)
+    {
+        *failures -= 1;
+        return ("503 Service Unavailable", "text/plain", Bytes::new());
+    }
+    match state.contents.get(cid) {
+        Some(content) => ("200 OK", "application/octet-stream", content.clone()),
+        None => ("404 Not Found", "text/plain", Bytes::new()),
+    }
+}
@@ -0,0 +1,34 @@
+use blockfrost::{AssetDetails, AssetPolicy, BlockFrostApi};
+use futures::{future::BoxFuture, FutureExt};
+
+/// Where the onchain data of the assets comes from: Blockfrost, or a stand-in for tests
+/// (see `mock::MockServer::onchain`, behind the `test-util` feature)
+pub trait Onchain: Send + Sync {
+    /// the assets minted under a policy id
+    fn assets_policy_by_id<'a>(
+        &'a self,
+        policy_id: &'a str,
+    ) -> BoxFuture<'a, blockfrost::Result<Vec<AssetPolicy>>>;
+
+    /// an asset with its onchain metadata
+    fn assets_by_id<'a>(
+        &'a self,
+        asset: &'a str,
+    ) -> BoxFuture<'a, blockfrost::Result<AssetDetails>>;
+}
+
+impl Onchain for BlockFrostApi {
+    fn assets_policy_by_id<'a>(
+        &'a self,
+        policy_id: &'a str,
+    ) -> BoxFuture<'a, blockfrost::Result<Vec<AssetPolicy>>> {
+        BlockFrostApi::assets_policy_by_id(self, policy_id).boxed()
+    }
+
+    fn assets_by_id<'a>(
+        &'a self,
+        asset: &'a str,
+    ) -> BoxFuture<'a, blockfrost::Result<AssetDetails>> {
+        BlockFrostApi::assets_by_id(self, asset).boxed()
+    }
+}
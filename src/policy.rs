@@ -0,0 +1,136 @@
+use crate::image::ImageFormat;
+use serde::Deserialize;
+
+/// Acceptance policy for covers (e.g. a storefront requiring portrait png or jpeg covers),
+/// from the `[cover_policy]` configuration section
+/// ```toml
+/// [cover_policy]
+/// formats = ["png", "jpeg"]
+/// min_aspect_ratio = 0.5 # width / height
+/// max_aspect_ratio = 0.8
+/// quarantine = true
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CoverPolicy {
+    /// accepted formats, any if not set
+    pub formats: Option<Vec<String>>,
+    pub min_aspect_ratio: Option<f64>,
+    pub max_aspect_ratio: Option<f64>,
+    /// store the non-conforming covers in the `quarantine/` directory instead of the work dir
+    #[serde(default)]
+    pub quarantine: bool,
+}
+
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+impl CoverPolicy {
+    /// checks a cover content against the policy, returning the violations found
+    pub fn check(&self, data: &[u8]) -> Vec<String> {
+        let mut violations = Vec::new();
+        let format = ImageFormat::sniff(data);
+
+        if let Some(formats) = &self.formats {
+            let allowed = format.is_some_and(|format| {
+                formats
+                    .iter()
+                    .any(|name| ImageFormat::parse(name) == Some(format))
+            });
+            if !allowed {
+                violations.push(match format {
+                    Some(format) => format!("format {} not allowed", format.extension()),
+                    None => "unknown format".to_owned(),
+                });
+            }
+        }
+
+        if self.min_aspect_ratio.is_some() || self.max_aspect_ratio.is_some() {
+            match format.and_then(|format| format.dimensions(data)) {
+                Some((width, height)) if height > 0 => {
+                    let ratio = width as f64 / height as f64;
+                    let too_narrow = self.min_aspect_ratio.is_some_and(|min| ratio < min);
+                    let too_wide = self.max_aspect_ratio.is_some_and(|max| ratio > max);
+                    if too_narrow || too_wide {
+                        violations.push(format!(
+                            "aspect ratio {:.2} ({}x{}) out of range",
+                            ratio, width, height
+                        ));
+                    }
+                }
+                _ => violations.push("unknown dimensions".to_owned()),
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data
+    }
+
+    /// portrait png or jpeg covers, as in the `[cover_policy]` example
+    fn storefront() -> CoverPolicy {
+        CoverPolicy {
+            formats: Some(vec!["png".to_owned(), "jpeg".to_owned()]),
+            min_aspect_ratio: Some(0.5),
+            max_aspect_ratio: Some(0.8),
+            quarantine: true,
+        }
+    }
+
+    #[test]
+    fn portrait_cover_is_accepted() {
+        assert!(storefront().check(&png(600, 900)).is_empty());
+    }
+
+    #[test]
+    fn landscape_cover_is_flagged() {
+        assert_eq!(
+            storefront().check(&png(900, 600)),
+            ["aspect ratio 1.50 (900x600) out of range"]
+        );
+        assert_eq!(
+            storefront().check(&png(300, 900)),
+            ["aspect ratio 0.33 (300x900) out of range"]
+        );
+    }
+
+    #[test]
+    fn format_not_allowed() {
+        //a portrait gif
+        assert_eq!(
+            storefront().check(b"GIF89a\x58\x02\x84\x03"),
+            ["format gif not allowed"]
+        );
+    }
+
+    #[test]
+    fn unknown_format_and_dimensions() {
+        assert_eq!(
+            storefront().check(b"not an image"),
+            ["unknown format", "unknown dimensions"]
+        );
+        //an allowed format whose header is cut short
+        assert_eq!(
+            storefront().check(&png(600, 900)[..20]),
+            ["unknown dimensions"]
+        );
+        //without a format list only the dimensions are checked
+        let policy = CoverPolicy {
+            formats: None,
+            ..storefront()
+        };
+        assert_eq!(policy.check(b"<svg/>"), ["unknown dimensions"]);
+    }
+
+    #[test]
+    fn empty_policy_accepts_anything() {
+        assert!(CoverPolicy::default().check(b"not an image").is_empty());
+    }
+}
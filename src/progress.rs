@@ -0,0 +1,134 @@
+use crate::i18n::{Lang, Message};
+use serde::Serialize;
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+/// Machine readable progress of a download run, sent as one json object per line (NDJSON)
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent<'a> {
+    Downloaded {
+        asset: &'a str,
+        cid: &'a str,
+        file: &'a str,
+        bytes: usize,
+    },
+    /// the cover cid changed, the previous file was archived
+    Superseded {
+        asset: &'a str,
+        previous_cid: &'a str,
+        cid: &'a str,
+        archived: &'a str,
+    },
+    AlreadyDownloaded {
+        asset: &'a str,
+    },
+    /// same cover as an asset already downloaded
+    Duplicate {
+        asset: &'a str,
+        cid: &'a str,
+        /// asset recorded in the manifest with the cover file, the duplicate is recorded as its alias
+        alias_of: Option<&'a str>,
+        /// downloaded for nothing
+        bytes: usize,
+    },
+    NoCover {
+        asset: &'a str,
+    },
+    /// the gateway refused the cover (e.g. 404 for a cid it can't find), the run goes on with the next asset
+    DownloadFailed {
+        asset: &'a str,
+        cid: &'a str,
+        error: &'a str,
+    },
+    /// the publisher expects a different cid than the onchain one, `same_content` if the downloaded
+    /// cover was compared with the content of the expected cid (not when the gateway doesn't have it)
+    ExpectedCidMismatch {
+        asset: &'a str,
+        expected: &'a str,
+        onchain: Option<&'a str>,
+        same_content: Option<bool>,
+    },
+    /// the cover doesn't conform to the cover policy, `quarantined` if it was stored apart
+    CoverPolicyViolation {
+        asset: &'a str,
+        violations: &'a [String],
+        quarantined: bool,
+    },
+    /// skipped, another worker splitting the collection has it
+    ClaimedByOtherWorker {
+        asset: &'a str,
+        worker: &'a str,
+    },
+    CollectionFinished {
+        policy_id: &'a str,
+        files: u32,
+    },
+}
+
+/// Where progress events go: nowhere, or a local socket / named pipe a GUI wrapper listens on,
+/// so the feed doesn't get mixed with the human readable output
+#[derive(Default)]
+pub struct ProgressSink {
+    writer: Mutex<Option<Box<dyn Write + Send>>>,
+    lang: Lang,
+}
+
+impl ProgressSink {
+    /// connects to a unix socket, or opens a named pipe (fifo), at `path`
+    pub fn connect(path: &Path) -> io::Result<ProgressSink> {
+        Ok(ProgressSink {
+            writer: Mutex::new(Some(open_writer(path)?)),
+            lang: Lang::default(),
+        })
+    }
+
+    /// language of the message saying the feed stopped (default: english)
+    pub fn with_lang(mut self, lang: Lang) -> ProgressSink {
+        self.lang = lang;
+        self
+    }
+
+    /// sends an event, the run isn't interrupted if the listener goes away: the feed just stops
+    pub fn emit(&self, event: &ProgressEvent) {
+        let mut writer = self.writer.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(out) = writer.as_mut() {
+            let sent = serde_json::to_vec(event)
+                .map_err(io::Error::from)
+                .and_then(|mut line| {
+                    line.push(b'\n');
+                    out.write_all(&line)?;
+                    out.flush()
+                });
+            if let Err(err) = sent {
+                let error = err.to_string();
+                println!(
+                    "{}",
+                    Message::ProgressFeedStopped { error: &error }.text(self.lang)
+                );
+                *writer = None;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_writer(path: &Path) -> io::Result<Box<dyn Write + Send>> {
+    use std::os::unix::{fs::FileTypeExt, net::UnixStream};
+
+    if std::fs::metadata(path)?.file_type().is_socket() {
+        Ok(Box::new(UnixStream::connect(path)?))
+    } else {
+        Ok(Box::new(OpenOptions::new().write(true).open(path)?))
+    }
+}
+
+#[cfg(not(unix))]
+fn open_writer(path: &Path) -> io::Result<Box<dyn Write + Send>> {
+    //windows named pipes (`\\.\pipe\<name>`) open as files
+    Ok(Box::new(OpenOptions::new().write(true).open(path)?))
+}
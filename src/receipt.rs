@@ -0,0 +1,51 @@
+use crate::{storage, timestamp};
+use reqwest::header::HeaderMap;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::io;
+
+/// Writes `<filename>.receipt.json` with what's needed for someone else to re-fetch the cover
+/// and confirm they get the same bytes: gateway url, response headers, content hash, tool version and time
+pub async fn write_receipt(
+    filename: &str,
+    asset: &str,
+    cid: &str,
+    url: &str,
+    headers: &HeaderMap,
+    data: &[u8],
+) -> io::Result<()> {
+    let headers: Map<String, Value> = headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                Value::from(String::from_utf8_lossy(value.as_bytes())),
+            )
+        })
+        .collect();
+
+    let receipt = json!({
+        "asset": asset,
+        "cid": cid,
+        "url": url,
+        "response_headers": headers,
+        "sha256": sha256_hex(data),
+        "size": data.len(),
+        "tool": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "downloaded_at": timestamp::now(),
+    });
+
+    let content = serde_json::to_vec_pretty(&receipt)?;
+    storage::write_file(&(filename.to_owned() + ".receipt.json"), &content).await
+}
+
+/// hex encoded sha2-256 of the content
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+/// lowercase hex encoding
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
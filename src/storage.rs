@@ -0,0 +1,218 @@
+use crate::{image::ImageFormat, timestamp};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Temporary file that is removed when dropped, unless it was persisted under its final name.
+/// Guarantees that a cancelled write (the future dropped at any await point) never leaves
+/// a partial file behind, neither under the temp name nor under the final one.
+pub struct TempFile {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl TempFile {
+    pub fn new(path: impl AsRef<Path>) -> TempFile {
+        TempFile {
+            path: path.as_ref().to_path_buf(),
+            persisted: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// moves the temp file to its final name, from there on the guard doesn't own the file.
+    /// Blocking, to be called from `spawn_blocking` along with the write as [`write_file`] does
+    pub fn persist_blocking(mut self, filename: impl AsRef<Path>) -> io::Result<()> {
+        fs::rename(&self.path, filename)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            //the file may not exist yet (or was already renamed), nothing to clean up then
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Writes `data` to a temp file and renames it to `filename`, so the final name only ever
+/// points to complete content.
+/// Both happen in a single blocking task owning the temp file: blocking file operations can't be
+/// interrupted, so once started the task runs to the rename (or cleans up) even if the write is cancelled
+pub async fn write_file(filename: &str, data: &[u8]) -> io::Result<()> {
+    let temp = TempFile::new(filename.to_owned() + ".tmp");
+    let filename = filename.to_owned();
+    let data = data.to_vec();
+    tokio::task::spawn_blocking(move || {
+        fs::write(temp.path(), data)?;
+        temp.persist_blocking(filename)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+pub const VERSIONS_DIR: &str = "versions";
+
+/// Moves a superseded cover file to the work dir `versions/` area, named after the asset, the time
+/// it was superseded and the cid it corresponded to, keeping its extension (`<asset>.<timestamp>.<cid>.<ext>`)
+pub fn archive_version(
+    work_dir: &Path,
+    file: &Path,
+    asset: &str,
+    cid: &str,
+) -> io::Result<PathBuf> {
+    let versions_dir = work_dir.join(VERSIONS_DIR);
+    fs::create_dir_all(&versions_dir)?;
+    let mut versioned_name = format!(
+        "{}.{}.{}",
+        asset,
+        timestamp::to_compact(timestamp::unix_now()),
+        cid
+    );
+    if let Some(extension) = image_extension(file) {
+        versioned_name = versioned_name + "." + extension;
+    }
+    let versioned = versions_dir.join(versioned_name);
+    fs::rename(file, &versioned)?;
+    Ok(versioned)
+}
+
+/// File name for a cover: the asset id with the extension of the content format, if recognized
+pub fn cover_file_name(asset: &str, data: &[u8]) -> String {
+    match ImageFormat::sniff(data) {
+        Some(format) => asset.to_owned() + "." + format.extension(),
+        None => asset.to_owned(),
+    }
+}
+
+/// Finds the cover file of an asset in the work dir, with or without extension
+/// (older runs saved the covers without one)
+pub fn find_cover(work_dir: &Path, asset: &str) -> Option<PathBuf> {
+    std::iter::once(work_dir.join(asset))
+        .chain(
+            ImageFormat::ALL
+                .iter()
+                .map(|format| work_dir.join(asset.to_owned() + "." + format.extension())),
+        )
+        .find(|file| file.exists())
+}
+
+/// the file extension, if it's one of the recognized image formats
+pub fn image_extension(file: &Path) -> Option<&'static str> {
+    let extension = file.extension()?.to_str()?;
+    ImageFormat::ALL
+        .iter()
+        .map(|format| format.extension())
+        .find(|known| *known == extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{future::Future, time::Duration};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("book_cli-storage-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// polls the write `polls` times (letting the blocking work go on in between), then drops it.
+    /// The runtime is shut down before returning, which waits for the blocking tasks
+    fn cancel_after<F: Future<Output = io::Result<()>>>(write: F, polls: usize) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut write = Box::pin(write);
+            for _ in 0..polls {
+                if futures::poll!(&mut write).is_ready() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        });
+    }
+
+    /// no temp file left, and the target either absent or with one of the complete contents
+    fn assert_consistent(dir: &Path, target: &Path, contents: &[&[u8]]) {
+        let temp_files: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|file| file.to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(temp_files.is_empty(), "left behind {:?}", temp_files);
+        if let Ok(content) = fs::read(target) {
+            assert!(contents.contains(&&content[..]), "partial content");
+        }
+    }
+
+    #[test]
+    fn cancelled_before_the_write() {
+        let dir = test_dir("before");
+        let target = dir.join("cover.png");
+        cancel_after(write_file(&target.to_string_lossy(), b"cover"), 0);
+        assert_consistent(&dir, &target, &[]);
+        assert!(!target.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_between_the_write_and_the_rename() {
+        //the first poll starts the blocking task, which writes and renames on its own
+        let dir = test_dir("between");
+        let target = dir.join("cover.png");
+        cancel_after(write_file(&target.to_string_lossy(), b"cover"), 1);
+        assert_consistent(&dir, &target, &[b"cover"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_after_the_rename() {
+        let dir = test_dir("after");
+        let target = dir.join("cover.png");
+        cancel_after(write_file(&target.to_string_lossy(), b"cover"), usize::MAX);
+        assert_consistent(&dir, &target, &[b"cover"]);
+        assert_eq!(fs::read(&target).unwrap(), b"cover");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_at_any_point_replacing_a_file() {
+        let dir = test_dir("replacing");
+        let target = dir.join("cover.png");
+        let new = vec![7u8; 1 << 20];
+        for polls in 0..20 {
+            fs::write(&target, b"old cover").unwrap();
+            cancel_after(write_file(&target.to_string_lossy(), &new), polls);
+            assert_consistent(&dir, &target, &[b"old cover", &new]);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn temp_file_removed_unless_persisted() {
+        let dir = test_dir("temp");
+        let temp = TempFile::new(dir.join("cover.tmp"));
+        fs::write(temp.path(), b"partial").unwrap();
+        drop(temp);
+        assert!(!dir.join("cover.tmp").exists());
+
+        let temp = TempFile::new(dir.join("cover.tmp"));
+        fs::write(temp.path(), b"cover").unwrap();
+        temp.persist_blocking(dir.join("cover.png")).unwrap();
+        assert_consistent(&dir, &dir.join("cover.png"), &[b"cover"]);
+        assert!(dir.join("cover.png").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{ProgressEvent, TransferStats};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// Counters of a download run, tallied from its progress events
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    downloaded: AtomicU32,
+    superseded: AtomicU32,
+    already_downloaded: AtomicU32,
+    duplicates: AtomicU32,
+    no_cover: AtomicU32,
+    expected_cid_mismatches: AtomicU32,
+    policy_violations: AtomicU32,
+    claimed_by_other_workers: AtomicU32,
+    download_failed: AtomicU32,
+    useful_bytes: AtomicU64,
+    duplicate_bytes: AtomicU64,
+    failed_bytes: AtomicU64,
+    stalled_transfers: AtomicU32,
+}
+
+/// Snapshot of the run counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunCounts {
+    pub downloaded: u32,
+    pub superseded: u32,
+    pub already_downloaded: u32,
+    pub duplicates: u32,
+    pub no_cover: u32,
+    pub expected_cid_mismatches: u32,
+    pub policy_violations: u32,
+    pub claimed_by_other_workers: u32,
+    /// covers the gateway refused
+    pub download_failed: u32,
+    /// bytes of the covers downloaded
+    pub useful_bytes: u64,
+    /// bytes downloaded for covers that turned out to be duplicates
+    pub duplicate_bytes: u64,
+    /// bytes received by failed transfers (e.g. truncated bodies)
+    pub failed_bytes: u64,
+    /// transfers aborted for going below the minimum throughput
+    pub stalled_transfers: u32,
+}
+
+impl RunCounts {
+    /// the counters by name (snake_case), in the order they are reported
+    pub fn outcomes(&self) -> [(&'static str, u32); 9] {
+        [
+            ("downloaded", self.downloaded),
+            ("superseded", self.superseded),
+            ("already_downloaded", self.already_downloaded),
+            ("duplicate", self.duplicates),
+            ("no_cover", self.no_cover),
+            ("expected_cid_mismatch", self.expected_cid_mismatches),
+            ("cover_policy_violation", self.policy_violations),
+            ("claimed_by_other_worker", self.claimed_by_other_workers),
+            ("download_failed", self.download_failed),
+        ]
+    }
+
+    /// the transferred bytes by kind, in the order they are reported
+    pub fn transferred(&self) -> [(&'static str, u64); 3] {
+        [
+            ("useful", self.useful_bytes),
+            ("duplicate", self.duplicate_bytes),
+            ("failed", self.failed_bytes),
+        ]
+    }
+
+    /// bytes transferred for nothing, duplicates and failed transfers
+    pub fn wasted_bytes(&self) -> u64 {
+        self.duplicate_bytes + self.failed_bytes
+    }
+}
+
+impl RunSummary {
+    pub fn count(&self, event: &ProgressEvent) {
+        match event {
+            ProgressEvent::Downloaded { bytes, .. } => {
+                self.useful_bytes
+                    .fetch_add(*bytes as u64, Ordering::Relaxed);
+            }
+            ProgressEvent::Duplicate { bytes, .. } => {
+                self.duplicate_bytes
+                    .fetch_add(*bytes as u64, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+        let counter = match event {
+            ProgressEvent::Downloaded { .. } => &self.downloaded,
+            ProgressEvent::Superseded { .. } => &self.superseded,
+            ProgressEvent::AlreadyDownloaded { .. } => &self.already_downloaded,
+            ProgressEvent::Duplicate { .. } => &self.duplicates,
+            ProgressEvent::NoCover { .. } => &self.no_cover,
+            ProgressEvent::ExpectedCidMismatch { .. } => &self.expected_cid_mismatches,
+            ProgressEvent::CoverPolicyViolation { .. } => &self.policy_violations,
+            ProgressEvent::ClaimedByOtherWorker { .. } => &self.claimed_by_other_workers,
+            ProgressEvent::DownloadFailed { .. } => &self.download_failed,
+            ProgressEvent::CollectionFinished { .. } => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// adds the failed transfers, as counted by a fetcher (see [`crate::CoverFetcher::transfers`])
+    pub fn add_transfers(&self, transfers: &TransferStats) {
+        self.failed_bytes
+            .fetch_add(transfers.failed_bytes(), Ordering::Relaxed);
+        self.stalled_transfers
+            .fetch_add(transfers.stalled(), Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> RunCounts {
+        RunCounts {
+            downloaded: self.downloaded.load(Ordering::Relaxed),
+            superseded: self.superseded.load(Ordering::Relaxed),
+            already_downloaded: self.already_downloaded.load(Ordering::Relaxed),
+            duplicates: self.duplicates.load(Ordering::Relaxed),
+            no_cover: self.no_cover.load(Ordering::Relaxed),
+            expected_cid_mismatches: self.expected_cid_mismatches.load(Ordering::Relaxed),
+            policy_violations: self.policy_violations.load(Ordering::Relaxed),
+            claimed_by_other_workers: self.claimed_by_other_workers.load(Ordering::Relaxed),
+            download_failed: self.download_failed.load(Ordering::Relaxed),
+            useful_bytes: self.useful_bytes.load(Ordering::Relaxed),
+            duplicate_bytes: self.duplicate_bytes.load(Ordering::Relaxed),
+            failed_bytes: self.failed_bytes.load(Ordering::Relaxed),
+            stalled_transfers: self.stalled_transfers.load(Ordering::Relaxed),
+        }
+    }
+}
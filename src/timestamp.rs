@@ -0,0 +1,109 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// current UTC time as an RFC 3339 timestamp (e.g. `2024-01-01T10:00:00Z`)
+pub fn now() -> String {
+    to_rfc3339(unix_now())
+}
+
+/// seconds since the unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// timestamp without separators, safe to use in file names (e.g. `20240101T100000Z`)
+pub fn to_compact(secs: u64) -> String {
+    to_rfc3339(secs).replace(['-', ':'], "")
+}
+
+/// RFC 3339 UTC timestamp for a date (`2024-01-01`, its last second) or a timestamp as recorded
+/// (`2024-01-01T10:00:00Z`), comparable with the recorded ones
+pub fn parse_point_in_time(input: &str) -> Option<String> {
+    let is_shaped = |shape: &str| {
+        input.len() == shape.len()
+            && input
+                .bytes()
+                .zip(shape.bytes())
+                .all(|(char, shape)| match shape {
+                    b'0' => char.is_ascii_digit(),
+                    _ => char == shape,
+                })
+    };
+    if is_shaped("0000-00-00") {
+        Some(input.to_owned() + "T23:59:59Z")
+    } else if is_shaped("0000-00-00T00:00:00Z") {
+        Some(input.to_owned())
+    } else {
+        None
+    }
+}
+
+/// formats seconds since the unix epoch as an RFC 3339 UTC timestamp
+pub fn to_rfc3339(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+// days since the epoch to (year, month, day), see http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_the_epoch_and_a_leap_day() {
+        assert_eq!(to_rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(to_rfc3339(951827696), "2000-02-29T12:34:56Z");
+        //the second after the leap day
+        assert_eq!(to_rfc3339(951868800), "2000-03-01T00:00:00Z");
+        assert_eq!(to_compact(951827696), "20000229T123456Z");
+    }
+
+    #[test]
+    fn parses_dates_and_timestamps() {
+        assert_eq!(
+            parse_point_in_time("2024-01-01").as_deref(),
+            Some("2024-01-01T23:59:59Z")
+        );
+        assert_eq!(
+            parse_point_in_time("2024-01-01T10:00:00Z").as_deref(),
+            Some("2024-01-01T10:00:00Z")
+        );
+        for input in [
+            "",
+            "2024-1-1",
+            "2024/01/01",
+            "20240101",
+            "2024-01-01T10:00:00",
+            "2024-01-01T10:00:00+01:00",
+            "2024-01-01 10:00:00Z",
+            "2024-01-01T10:00Z",
+            "yyyy-mm-dd",
+        ] {
+            assert_eq!(parse_point_in_time(input), None, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{cid, receipt::sha256_hex, CoverFetcher, Manifest};
+use blockfrost::AssetPolicy;
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use std::{error::Error, fs, path::Path, sync::Arc};
+
+/// Result of verifying one asset's cover against the content its onchain cid points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyEvent {
+    Ok {
+        asset: String,
+    },
+    /// the asset has a high-res cover but there's no file for it
+    Missing {
+        asset: String,
+    },
+    /// the file content differs from the one fetched by cid (hex encoded sha2-256)
+    HashMismatch {
+        asset: String,
+        expected: String,
+        actual: String,
+    },
+    Unreadable {
+        asset: String,
+        error: String,
+    },
+}
+
+impl CoverFetcher {
+    /// Verifies the covers of a collection stored in `dir` (as recorded in its manifest, or one file
+    /// per asset, named by asset id) yielding an event for each asset with a high-res cover. Errors
+    /// reaching Blockfrost or the gateway are yielded as `Err` so the caller decides whether to keep going.
+    pub fn verify_collection<'a>(
+        &'a self,
+        policy_id: &'a str,
+        dir: &'a Path,
+    ) -> impl Stream<Item = Result<VerifyEvent, Box<dyn Error + Send + Sync>>> + Send + 'a {
+        let manifest = match Manifest::load(dir) {
+            Ok(manifest) => Arc::new(manifest),
+            Err(err) => return stream::once(future::err(err.to_string().into())).left_stream(),
+        };
+        stream::once(self.assets(policy_id))
+            .map_ok(|assets| stream::iter(assets.into_iter().map(Ok::<_, blockfrost::Error>)))
+            .try_flatten()
+            .map_err(|err| err.into())
+            .try_filter(|asset: &AssetPolicy| {
+                future::ready(asset.quantity.parse::<u64>().unwrap_or(0) > 0)
+            })
+            .and_then(move |asset| self.verify_asset(asset, dir, manifest.clone()))
+            .try_filter_map(future::ok)
+            .right_stream()
+    }
+
+    async fn verify_asset(
+        &self,
+        asset: AssetPolicy,
+        dir: &Path,
+        manifest: Arc<Manifest>,
+    ) -> Result<Option<VerifyEvent>, Box<dyn Error + Send + Sync>> {
+        let cover = match self.cover(&asset.asset).await? {
+            Some(cover) => cover,
+            None => return Ok(None),
+        };
+
+        let filename = match manifest.cover_file(dir, &asset.asset) {
+            Some(filename) => filename,
+            None => return Ok(Some(VerifyEvent::Missing { asset: asset.asset })),
+        };
+        let file_data = match fs::read(&filename) {
+            Ok(data) => data,
+            Err(err) => {
+                return Ok(Some(VerifyEvent::Unreadable {
+                    asset: asset.asset,
+                    error: err.to_string(),
+                }))
+            }
+        };
+
+        //the file has the cid if it's the content addressed by it, added to ipfs with the usual settings
+        let onchain = cid::decode(&cover.cid);
+        if onchain.is_some_and(|onchain| cid::local_cids(&file_data).contains(&onchain)) {
+            return Ok(Some(VerifyEvent::Ok { asset: asset.asset }));
+        }
+
+        //otherwise the gateway serves the content addressed by the cid, so it's the reference to compare against
+        let (_, expected_data) = self.download(&cover.cid).await?;
+        let expected = sha256_hex(&expected_data);
+        let actual = sha256_hex(&file_data);
+        if expected == actual {
+            Ok(Some(VerifyEvent::Ok { asset: asset.asset }))
+        } else {
+            Ok(Some(VerifyEvent::HashMismatch {
+                asset: asset.asset,
+                expected,
+                actual,
+            }))
+        }
+    }
+}
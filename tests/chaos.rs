@@ -0,0 +1,173 @@
+//! Runs interrupted by the injected Blockfrost and gateway faults (5xx, timeouts, 429s, truncated
+//! bodies) converge to a consistent work dir when run again: every cover recorded, complete, and no
+//! temp files left. The mock server stands in for Blockfrost and the gateway, each run goes through
+//! the steps of the cli for an asset: listing the collection, reading the cover from the metadata,
+//! downloading and writing it, then recording it.
+//! Run with `cargo test --features test-util`
+use blockfrost::BlockFrostApi;
+use book_cli::{
+    mock::MockServer, receipt::sha256_hex, storage, Chaos, CoverFetcher, Manifest, NoRetry,
+};
+use serde_json::json;
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const POLICY_ID: &str = "0000000000000000000000000000000000000000000000000000b00c";
+
+const COVERS: [(&str, &str, &[u8]); 3] = [
+    ("0001", "QmCover1", b"\x89PNG\r\n\x1a\n first cover"),
+    ("0002", "QmCover2", b"\xff\xd8\xff second cover"),
+    ("0003", "QmCover3", b"third cover, no known format"),
+];
+
+fn asset_id(name: &str) -> String {
+    POLICY_ID.to_owned() + name
+}
+
+async fn mock_gateway(content_length: bool) -> std::io::Result<MockServer> {
+    let mock = MockServer::start().await?;
+    for (name, cid, content) in COVERS {
+        mock.add_asset(
+            &asset_id(name),
+            json!({ "name": name, "files": [{ "src": format!("ipfs://{}", cid) }] }),
+        );
+        mock.add_content(cid, content);
+    }
+    if !content_length {
+        mock.without_content_length();
+    }
+    Ok(mock)
+}
+
+/// every fault fails the download, nothing is retried
+fn fetcher(mock: &MockServer, seed: u64, rate: f64) -> CoverFetcher {
+    let api = BlockFrostApi::new("<project id>", Default::default());
+    CoverFetcher::new(api, &mock.gateway_url())
+        .with_onchain(mock.onchain())
+        .with_retry_policy(NoRetry)
+        .with_chaos(Chaos::new(seed).with_rate(rate))
+}
+
+fn work_dir(name: &str, seed: u64) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "book_cli-chaos-{}-{}-{}",
+        name,
+        std::process::id(),
+        seed
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// a run downloading the covers not recorded yet, as the cli does (the file written, then recorded),
+/// interrupted by the first failure
+async fn run(fetcher: &CoverFetcher, work_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut manifest = Manifest::load(work_dir)?;
+    for asset in fetcher.assets(POLICY_ID).await? {
+        if manifest.cover_file(work_dir, &asset.asset).is_some() {
+            continue;
+        }
+        let cover = fetcher.cover(&asset.asset).await?.ok_or("no cover")?;
+        let (_, data) = fetcher.download(&cover.cid).await?;
+        let file_name = storage::cover_file_name(&asset.asset, &data);
+        storage::write_file(&work_dir.join(&file_name).to_string_lossy(), &data).await?;
+        manifest.record(&asset.asset, &cover.cid, &file_name, &data);
+        manifest.save(work_dir).await?;
+    }
+    Ok(())
+}
+
+/// runs again until one completes, then checks the work dir
+async fn converges(content_length: bool) {
+    let mock = mock_gateway(content_length).await.unwrap();
+    for seed in 0..20 {
+        let work_dir = work_dir(if content_length { "length" } else { "close" }, seed);
+        let fetcher = fetcher(&mock, seed, 0.5);
+        let mut runs = 0;
+        while let Err(err) = run(&fetcher, &work_dir).await {
+            runs += 1;
+            assert!(runs < 100, "seed {}: still failing with {}", seed, err);
+        }
+
+        let manifest = Manifest::load(&work_dir).unwrap();
+        assert_eq!(manifest.covers().len(), COVERS.len(), "seed {}", seed);
+        for (name, cid, content) in COVERS {
+            let entry = manifest.get(&asset_id(name)).unwrap();
+            assert_eq!(entry.cid, cid, "seed {}", seed);
+            assert_eq!(entry.sha256, sha256_hex(content), "seed {}", seed);
+            let file = manifest.cover_file(&work_dir, &asset_id(name)).unwrap();
+            assert_eq!(fs::read(file).unwrap(), content, "seed {}", seed);
+        }
+        let temp_files = fs::read_dir(&work_dir)
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().ends_with(".tmp")
+            })
+            .count();
+        assert_eq!(temp_files, 0, "seed {}", seed);
+        fs::remove_dir_all(&work_dir).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn converges_with_content_length() {
+    converges(true).await;
+}
+
+#[tokio::test]
+async fn converges_without_content_length() {
+    converges(false).await;
+}
+
+#[tokio::test]
+async fn faulty_downloads_never_succeed() {
+    for content_length in [true, false] {
+        let mock = mock_gateway(content_length).await.unwrap();
+        for seed in 0..20 {
+            let fetcher = fetcher(&mock, seed, 1.0);
+            for (_, cid, _) in COVERS {
+                assert!(fetcher.download(cid).await.is_err(), "seed {}", seed);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn faulty_blockfrost_calls_never_succeed() {
+    let mock = mock_gateway(true).await.unwrap();
+    for seed in 0..20 {
+        let fetcher = fetcher(&mock, seed, 1.0);
+        assert!(fetcher.assets(POLICY_ID).await.is_err(), "seed {}", seed);
+        for (name, _, _) in COVERS {
+            assert!(
+                fetcher.cover(&asset_id(name)).await.is_err(),
+                "seed {}",
+                seed
+            );
+        }
+    }
+    //without faults, the mock answers as Blockfrost
+    let fetcher = fetcher(&mock, 0, 0.0);
+    assert_eq!(fetcher.assets(POLICY_ID).await.unwrap().len(), COVERS.len());
+    let cover = fetcher.cover(&asset_id("0001")).await.unwrap().unwrap();
+    assert_eq!(cover.cid, "QmCover1");
+}
+
+#[tokio::test]
+async fn truncated_bodies_count_as_failed_bytes() {
+    let mock = mock_gateway(false).await.unwrap();
+    let mut failed = false;
+    for seed in 0..20 {
+        let fetcher = fetcher(&mock, seed, 1.0);
+        for (_, cid, _) in COVERS {
+            let _ = fetcher.download(cid).await;
+        }
+        failed |= fetcher.transfers().failed_bytes() > 0;
+    }
+    assert!(failed, "no truncated body was injected");
+}
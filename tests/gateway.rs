@@ -0,0 +1,160 @@
+//! Transient gateway errors are retried, the covers the gateway refuses are not, and a gateway that
+//! stops answering or sends too slowly is given up on. Concurrent downloads of a cid are fetched once.
+//! Run with `cargo test --features test-util`
+use blockfrost::BlockFrostApi;
+use book_cli::{mock::MockServer, CoverFetcher, MinThroughput, NoRetry};
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn fetcher(mock: &MockServer) -> CoverFetcher {
+    let api = BlockFrostApi::new("<project id>", Default::default());
+    CoverFetcher::new(api, &mock.gateway_url())
+        .with_retry_policy(|| [Duration::ZERO; 3].into_iter())
+}
+
+#[tokio::test]
+async fn unavailable_is_retried() {
+    let mock = MockServer::start().await.unwrap();
+    mock.add_content("QmCover", &b"cover"[..]);
+    mock.fail_next("QmCover", 2);
+
+    let (_, cover) = fetcher(&mock).download("QmCover").await.unwrap();
+    assert_eq!(&cover[..], b"cover");
+    assert_eq!(mock.requests("QmCover"), 3);
+}
+
+#[tokio::test]
+async fn not_found_is_not_retried() {
+    let mock = MockServer::start().await.unwrap();
+
+    let err = fetcher(&mock).download("QmUnpinned").await.unwrap_err();
+    assert!(err.is_permanent());
+    assert_eq!(err.status().map(|status| status.as_u16()), Some(404));
+    assert_eq!(mock.requests("QmUnpinned"), 1);
+}
+
+#[tokio::test]
+async fn unavailable_after_retries_is_not_permanent() {
+    let mock = MockServer::start().await.unwrap();
+    mock.add_content("QmCover", &b"cover"[..]);
+    mock.fail_next("QmCover", 10);
+
+    let err = fetcher(&mock).download("QmCover").await.unwrap_err();
+    assert!(!err.is_permanent());
+    assert_eq!(mock.requests("QmCover"), 4);
+}
+
+#[tokio::test]
+async fn gateway_not_answering_stalls() {
+    //accepts the connections but never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let gateway = format!("http://{}/ipfs/", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let mut connections = Vec::new();
+        while let Ok((connection, _)) = listener.accept().await {
+            connections.push(connection);
+        }
+    });
+
+    let api = BlockFrostApi::new("<project id>", Default::default());
+    let fetcher = CoverFetcher::new(api, &gateway)
+        .with_retry_policy(NoRetry)
+        .with_min_throughput(MinThroughput {
+            gateway: gateway.clone(),
+            bytes_per_second: 1,
+            window_seconds: 1,
+        });
+    let download = tokio::time::timeout(Duration::from_secs(10), fetcher.download("QmCover"));
+    assert!(download.await.expect("not stalled").is_err());
+    assert_eq!(fetcher.transfers().stalled(), 1);
+}
+
+#[tokio::test]
+async fn concurrent_downloads_of_a_cid_fetch_it_once() {
+    let mock = MockServer::start().await.unwrap();
+    mock.add_content("QmCover", &b"cover"[..]);
+    let fetcher = fetcher(&mock);
+
+    let (first, second) = tokio::join!(fetcher.download("QmCover"), fetcher.download("QmCover"));
+    assert_eq!(&first.unwrap().1[..], b"cover");
+    assert_eq!(&second.unwrap().1[..], b"cover");
+    assert_eq!(mock.requests("QmCover"), 1);
+}
+
+#[tokio::test]
+async fn cancelled_first_download_is_still_shared() {
+    //answers each connection after a while, counting them
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let gateway = format!("http://{}/ipfs/", listener.local_addr().unwrap());
+    let connections = Arc::new(AtomicU32::new(0));
+    let accepted = connections.clone();
+    tokio::spawn(async move {
+        while let Ok((mut connection, _)) = listener.accept().await {
+            accepted.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                let mut request = [0; 1024];
+                let _ = connection.read(&mut request).await;
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                let _ = connection
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\ncover")
+                    .await;
+            });
+        }
+    });
+    let api = BlockFrostApi::new("<project id>", Default::default());
+    let fetcher = CoverFetcher::new(api, &gateway).with_retry_policy(NoRetry);
+
+    let mut first = Box::pin(fetcher.download("QmCover"));
+    let mut second = Box::pin(fetcher.download("QmCover"));
+    assert!(futures::poll!(first.as_mut()).is_pending());
+    assert!(futures::poll!(second.as_mut()).is_pending());
+    drop(first);
+
+    let (second, third) = tokio::join!(second, fetcher.download("QmCover"));
+    assert_eq!(&second.unwrap().1[..], b"cover");
+    assert_eq!(&third.unwrap().1[..], b"cover");
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+fn watched_fetcher(mock: &MockServer) -> CoverFetcher {
+    let api = BlockFrostApi::new("<project id>", Default::default());
+    CoverFetcher::new(api, &mock.gateway_url())
+        .with_retry_policy(NoRetry)
+        .with_min_throughput(MinThroughput {
+            gateway: mock.gateway_url(),
+            bytes_per_second: 1000,
+            window_seconds: 1,
+        })
+}
+
+#[tokio::test]
+async fn slow_transfer_above_the_minimum_completes() {
+    let mock = MockServer::start().await.unwrap();
+    mock.add_content("QmCover", vec![7u8; 6000]);
+    mock.slow_body(4000);
+
+    let fetcher = watched_fetcher(&mock);
+    let (_, cover) = fetcher.download("QmCover").await.unwrap();
+    assert_eq!(cover.len(), 6000);
+    assert_eq!(fetcher.transfers().stalled(), 0);
+}
+
+#[tokio::test]
+async fn slow_transfer_below_the_minimum_stalls() {
+    let mock = MockServer::start().await.unwrap();
+    mock.add_content("QmCover", vec![7u8; 6000]);
+    mock.slow_body(200);
+
+    let fetcher = watched_fetcher(&mock);
+    let download = tokio::time::timeout(Duration::from_secs(10), fetcher.download("QmCover"));
+    assert!(download.await.expect("not stalled").is_err());
+    assert_eq!(fetcher.transfers().stalled(), 1);
+    //the bytes received before giving up count as failed
+    assert!(fetcher.transfers().failed_bytes() > 0);
+}